@@ -0,0 +1,165 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Splitting and expansion of command-line arguments
+
+use std::fs;
+use std::path::{Path,PathBuf};
+
+/// Splits a command line into arguments, honouring single quotes, double quotes
+/// and backslash escapes
+///
+/// # Example
+/// ```
+/// use rpf::args;
+///
+/// assert_eq!(args::split(r#"-n 'a b' "c \"d\"" e\ f"#).unwrap(),
+///            vec!["-n", "a b", "c \"d\"", "e f"]);
+/// ```
+pub fn split(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut cur = String::new();
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => { quote = None; },
+            (Some('"'), '\\') | (None, '\\') => {
+                match chars.next() {
+                    Some(n) => { cur.push(n); in_arg = true; },
+                    None => { return Err("trailing backslash".to_string()); },
+                }
+            },
+            (Some(_), c) => { cur.push(c); },
+            (None, '\'') | (None, '"') => { quote = Some(c); in_arg = true; },
+            (None, c) if c.is_whitespace() => {
+                if in_arg { args.push(cur.clone()); cur.clear(); in_arg = false; }
+            },
+            (None, c) => { cur.push(c); in_arg = true; },
+        }
+    }
+    if quote.is_some() { return Err("unterminated quote".to_string()); }
+    if in_arg { args.push(cur); }
+    Ok(args)
+}
+
+/// Quotes `arg` so that `split` and POSIX shells read it back unchanged
+///
+/// # Example
+/// ```
+/// use rpf::args;
+///
+/// assert_eq!(args::quote("file.txt"), "file.txt");
+/// assert_eq!(args::quote("it's here"), r#"'it'\''s here'"#);
+/// assert_eq!(args::quote(""), "''");
+/// ```
+pub fn quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "@%+=:,./_-".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', r#"'\''"#))
+}
+
+/// Joins arguments into a command line, quoting them with `quote`
+///
+/// # Example
+/// ```
+/// use rpf::args;
+///
+/// assert_eq!(args::join(&["grep", "-e", "a b"]), "grep -e 'a b'");
+/// ```
+pub fn join<T: AsRef<str>>(args: &[T]) -> String {
+    let quoted: Vec<String> = args.iter().map(|a| quote(a.as_ref())).collect();
+    quoted.join(" ")
+}
+
+/// Replaces each `@file` argument with the arguments read from `file`. Files
+/// are split with `split`, lines starting with `#` are comments and `@file`
+/// arguments inside them are expanded relative to the including file. Nothing
+/// after `--` is expanded, and `@@arg` is passed on as `@arg`.
+///
+/// # Example
+/// ```
+/// use rpf::args;
+/// use std::fs;
+///
+/// fs::write("args-doc.rsp", "# defaults\n-v --name 'a b'\n").unwrap();
+/// assert_eq!(args::expand(vec!["@args-doc.rsp".to_string(), "x".to_string()]).unwrap(),
+///            vec!["-v", "--name", "a b", "x"]);
+/// fs::remove_file("args-doc.rsp").unwrap();
+/// ```
+pub fn expand(args: Vec<String>) -> Result<Vec<String>, String> {
+    let mut out = Vec::new();
+    expand_into(args, Path::new(""), &mut Vec::new(), &mut out)?;
+    Ok(out)
+}
+
+fn expand_into(args: Vec<String>, base: &Path, stack: &mut Vec<PathBuf>,
+               out: &mut Vec<String>) -> Result<bool, String> {
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            out.push(arg);
+            out.extend(iter);
+            return Ok(false);
+        } else if let Some(rest) = arg.strip_prefix("@@") {
+            out.push(format!("@{}", rest));
+        } else if let Some(name) = arg.strip_prefix('@').filter(|n| !n.is_empty()) {
+            let path = base.join(name);
+            let canon = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+            if stack.contains(&canon) {
+                return Err(format!("{}: response file includes itself", path.display()));
+            }
+            let text = match fs::read_to_string(&path) {
+                Ok(t) => { t },
+                Err(e) => { return Err(format!("{}: {}", path.display(), e)); },
+            };
+            let mut inner = Vec::new();
+            for line in text.lines().filter(|l| !l.trim_start().starts_with('#')) {
+                match split(line) {
+                    Ok(a) => { inner.extend(a); },
+                    Err(e) => { return Err(format!("{}: {}", path.display(), e)); },
+                }
+            }
+            stack.push(canon);
+            let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+            let more = expand_into(inner, &dir, stack, out)?;
+            stack.pop();
+            if !more {
+                out.extend(iter);
+                return Ok(false);
+            }
+        } else {
+            out.push(arg);
+        }
+    }
+    Ok(true)
+}
+
+#[test]
+fn test_args_split() {
+    assert_eq!(split("").unwrap(), Vec::<String>::new());
+    assert_eq!(split("a ''").unwrap(), vec!["a", ""]);
+    assert!(split("util 'a").is_err());
+    assert!(split("util a\\").is_err());
+}
+
+#[test]
+fn test_args_expand_nested() {
+    use utils::test;
+
+    fs::create_dir_all("test-args/sub").unwrap();
+    fs::write("test-args/top.rsp", "-a @sub/inner.rsp -d\n").unwrap();
+    fs::write("test-args/sub/inner.rsp", "-b\n# comment\n-c\n").unwrap();
+    fs::write("test-args/sub/loop.rsp", "@loop.rsp\n").unwrap();
+    let args = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+    assert_eq!(expand(args(&["@test-args/top.rsp", "@@x", "--", "@y"])).unwrap(),
+               vec!["-a", "-b", "-c", "-d", "@x", "--", "@y"]);
+    assert!(expand(args(&["@test-args/sub/loop.rsp"])).unwrap_err()
+            .ends_with("response file includes itself"));
+    assert!(expand(args(&["@test-args/missing"])).is_err());
+    test::remove("test-args");
+}
@@ -52,6 +52,7 @@ impl AsPath for str {
 }
 
 #[test]
+#[allow(noop_method_call)]
 fn test_as_path_string() {
     assert_eq!("/etc/test/dir".to_string().as_path().clone(),
         Path::new("/etc/test/dir"));
@@ -64,6 +65,7 @@ fn test_as_pathbuf_string() {
 }
 
 #[test]
+#[allow(noop_method_call)]
 fn test_as_path_str() {
     assert_eq!("/etc/test/dir".as_path().clone(),
         Path::new("/etc/test/dir"));
@@ -0,0 +1,136 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Build script support for capturing build information into `Prog`
+//!
+//! Calling `emit` from a program's `build.rs` sets the environment variables
+//! read by the `prog!` macro when the program is compiled.
+//!
+//! ```ignore
+//! // build.rs
+//! extern crate rpf;
+//!
+//! fn main() {
+//!     rpf::build::emit();
+//! }
+//! ```
+
+use std::env;
+use std::fs;
+use std::process::Command;
+use std::time::{SystemTime,UNIX_EPOCH};
+
+/// Information about how a program was built
+#[derive(Copy,Clone,Debug,Default,PartialEq)]
+pub struct Build {
+    /// Abbreviated git commit the program was built from
+    pub commit: &'static str,
+    /// Date of the build as `YYYY-MM-DD`
+    pub date: &'static str,
+    /// Target triple the program was built for
+    pub target: &'static str,
+    /// Enabled cargo features, separated by commas
+    pub features: &'static str,
+}
+
+impl Build {
+    /// A `Build` with no information
+    pub const fn empty() -> Build {
+        Build { commit: "", date: "", target: "", features: "" }
+    }
+
+    /// Returns the year of the build date
+    pub fn year(&self) -> Option<&'static str> {
+        let date = self.date;
+        if date.len() >= 4 && date[..4].chars().all(|c| c.is_ascii_digit()) {
+            Some(&date[..4])
+        } else {
+            None
+        }
+    }
+}
+
+/// Prints the `cargo:rustc-env` instructions for the build information read by
+/// `prog!`: `RPF_BUILD_COMMIT`, `RPF_BUILD_DATE`, `RPF_BUILD_YEAR`,
+/// `RPF_BUILD_TARGET` and `RPF_BUILD_FEATURES`. `SOURCE_DATE_EPOCH` is
+/// honoured for reproducible builds.
+pub fn emit() {
+    for (key, val) in vars().iter() {
+        println!("cargo:rustc-env={}={}", key, val);
+    }
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    for path in git_watch().iter() {
+        println!("cargo:rerun-if-changed={}", path);
+    }
+}
+
+// Files that change when a new commit is checked out or made: `HEAD` and the
+// branch it points to, which may live in `packed-refs`
+fn git_watch() -> Vec<String> {
+    let mut paths = vec![".git/HEAD".to_string()];
+    if let Ok(head) = fs::read_to_string(".git/HEAD") {
+        if let Some(name) = head.trim().strip_prefix("ref: ") {
+            paths.push(format!(".git/{}", name));
+            paths.push(".git/packed-refs".to_string());
+        }
+    }
+    // Cargo reruns the build script every time if a watched file is missing
+    paths.into_iter().filter(|p| fs::metadata(p).is_ok()).collect()
+}
+
+/// Returns the build information variables `emit` would set
+pub fn vars() -> Vec<(&'static str, String)> {
+    let commit = Command::new("git").args(["rev-parse", "--short", "HEAD"]).output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_default();
+    let secs = match env::var("SOURCE_DATE_EPOCH").ok().and_then(|s| s.parse::<u64>().ok()) {
+        Some(secs) => { secs },
+        None => {
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+        },
+    };
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(k, _)| k.strip_prefix("CARGO_FEATURE_")
+                    .map(|f| f.to_lowercase().replace('_', "-")))
+        .collect();
+    features.sort();
+    let date = date(secs);
+    vec![
+        ("RPF_BUILD_COMMIT", commit),
+        ("RPF_BUILD_YEAR", date[..4].to_string()),
+        ("RPF_BUILD_DATE", date),
+        ("RPF_BUILD_TARGET", env::var("TARGET").unwrap_or_default()),
+        ("RPF_BUILD_FEATURES", features.join(",")),
+    ]
+}
+
+// Converts seconds since the epoch to a `YYYY-MM-DD` civil date
+pub(crate) fn date(secs: u64) -> String {
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days / 146097;
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[test]
+fn test_build_date() {
+    assert_eq!(date(0), "1970-01-01");
+    assert_eq!(date(1438387200), "2015-08-01");
+    assert_eq!(date(951782400), "2000-02-29");
+}
+
+#[test]
+fn test_build_year() {
+    let build = Build { date: "2015-08-01", ..Build::empty() };
+    assert_eq!(build.year(), Some("2015"));
+    assert_eq!(Build::empty().year(), None);
+}
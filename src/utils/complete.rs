@@ -0,0 +1,528 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Shell completion scripts generated from a `Cmd`
+//!
+//! Static scripts contain every option, subcommand and fixed choice. Dynamic
+//! scripts instead call the program back with `DYNAMIC` followed by the words
+//! on the command line, which `Prog::parse_args` answers with `candidates`, so
+//! that `Hint::Dynamic` values can depend on context.
+
+use utils::{Cmd,Hint,Opt,PathMod};
+
+use std::fmt;
+use std::fs;
+use std::io::{self,Write};
+use std::path::{Path,PathBuf};
+use std::str::FromStr;
+
+/// Hidden option that makes `Prog::parse_args` print a static completion
+/// script for the named shell and exit
+pub static FLAG: Opt = Opt::value("generate-completion", "SHELL",
+                                  "Print a shell completion script and exit")
+    .hidden().choices(&["bash", "zsh", "fish", "powershell"]);
+
+/// First argument that makes `Prog::parse_args` print completion candidates
+/// for the words that follow it and exit, if the command includes `FLAG`
+pub const DYNAMIC: &str = "--rpf-complete";
+
+/// Shells that completion scripts can be generated for
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Shell, String> {
+        match &s.to_lowercase()[..] {
+            "bash" => { Ok(Shell::Bash) },
+            "zsh" => { Ok(Shell::Zsh) },
+            "fish" => { Ok(Shell::Fish) },
+            "powershell" | "pwsh" => { Ok(Shell::PowerShell) },
+            _ => { Err(format!("Unknown shell '{}'", s)) },
+        }
+    }
+}
+
+impl fmt::Display for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Shell::Bash => { write!(f, "bash") },
+            Shell::Zsh => { write!(f, "zsh") },
+            Shell::Fish => { write!(f, "fish") },
+            Shell::PowerShell => { write!(f, "powershell") },
+        }
+    }
+}
+
+/// Returns a static completion script for `cmd`
+///
+/// # Example
+/// ```
+/// use rpf::{Cmd,Opt,Hint};
+/// use rpf::complete::{self,Shell};
+///
+/// let cmd = Cmd::new("util")
+///     .opt(Opt::value("color", "WHEN", "Colorize").choices(&["always", "never"]));
+/// let script = complete::generate(Shell::Bash, &cmd);
+/// assert!(script.contains("compgen -W \"always never\""));
+/// assert!(script.ends_with("complete -F _util util\n"));
+/// ```
+pub fn generate(shell: Shell, cmd: &Cmd) -> String {
+    match shell {
+        Shell::Bash => { bash(cmd) },
+        Shell::Zsh => { zsh(cmd) },
+        Shell::Fish => { fish(cmd) },
+        Shell::PowerShell => { powershell(cmd) },
+    }
+}
+
+/// Returns a completion script for `cmd` that asks the program for candidates
+pub fn generate_dynamic(shell: Shell, cmd: &Cmd) -> String {
+    let name = cmd.name;
+    let func = ident(name);
+    match shell {
+        Shell::Bash => {
+            format!("_{f}() {{\n    local IFS=$'\\n'\n    \
+                     COMPREPLY=($({n} {d} -- \"${{COMP_WORDS[@]:1:COMP_CWORD}}\" \
+                     2>/dev/null))\n}}\ncomplete -o default -F _{f} {n}\n",
+                    f = func, n = name, d = DYNAMIC)
+        },
+        Shell::Zsh => {
+            format!("#compdef {n}\n_{f}() {{\n    local -a candidates\n    \
+                     candidates=(\"${{(@f)$({n} {d} -- \"${{(@)words[2,$CURRENT]}}\" \
+                     2>/dev/null)}}\")\n    \
+                     compadd -a candidates || _files\n}}\ncompdef _{f} {n}\n",
+                    f = func, n = name, d = DYNAMIC)
+        },
+        Shell::Fish => {
+            format!("complete -c {n} -f -a '({n} {d} -- (commandline -opc)[2..-1] \
+                     (commandline -ct))'\n", n = name, d = DYNAMIC)
+        },
+        Shell::PowerShell => {
+            format!("Register-ArgumentCompleter -Native -CommandName '{n}' -ScriptBlock {{\n    \
+                     param($wordToComplete, $commandAst, $cursorPosition)\n    \
+                     $words = @($commandAst.CommandElements | Select-Object -Skip 1 | \
+                     ForEach-Object {{ $_.ToString() }})\n    \
+                     if ($wordToComplete -eq '') {{ $words += '' }}\n    \
+                     & '{n}' {d} -- @words | ForEach-Object {{\n        \
+                     [System.Management.Automation.CompletionResult]::new($_, $_, \
+                     'ParameterValue', $_)\n    }}\n}}\n", n = name, d = DYNAMIC)
+        },
+    }
+}
+
+/// Writes a static completion script for `cmd` into `dir`, named as each
+/// shell expects, for use from a build script
+pub fn write<P: AsRef<Path>>(shell: Shell, cmd: &Cmd, dir: P) -> io::Result<PathBuf> {
+    fs::create_dir_all(&dir)?;
+    let file = match shell {
+        Shell::Bash => { cmd.name.to_string() },
+        Shell::Zsh => { format!("_{}", cmd.name) },
+        Shell::Fish => { format!("{}.fish", cmd.name) },
+        Shell::PowerShell => { format!("_{}.ps1", cmd.name) },
+    };
+    let path = dir.as_ref().join(file);
+    fs::File::create(&path)?.write_all(generate(shell, cmd).as_bytes())?;
+    Ok(path)
+}
+
+/// Returns completion candidates for the last of `words`, the words on the
+/// command line after the program name
+///
+/// # Example
+/// ```
+/// use rpf::{Cmd,Opt};
+/// use rpf::complete;
+///
+/// let cmd = Cmd::new("util")
+///     .opt(Opt::value("color", "WHEN", "Colorize").choices(&["always", "never"]))
+///     .subcommand(Cmd::new("add")).subcommand(Cmd::new("remove"));
+/// assert_eq!(complete::candidates(&cmd, &["--color", "a"]), vec!["always"]);
+/// assert_eq!(complete::candidates(&cmd, &["--color=n"]), vec!["--color=never"]);
+/// assert_eq!(complete::candidates(&cmd, &["r"]), vec!["remove"]);
+/// ```
+pub fn candidates<T: AsRef<str>>(cmd: &Cmd, words: &[T]) -> Vec<String> {
+    let (cur, done) = match words.split_last() {
+        Some((cur, done)) => { (cur.as_ref(), done) },
+        None => { ("", &words[..0]) },
+    };
+    let mut cmd = cmd;
+    let mut pending: Option<&Opt> = None;
+    let mut free = 0;
+    let mut dashdash = false;
+    for word in done.iter().map(|w| w.as_ref()) {
+        if pending.is_some() {
+            pending = None;
+        } else if dashdash {
+            free += 1;
+        } else if word == "--" {
+            dashdash = true;
+        } else if let Some(long) = word.strip_prefix("--") {
+            if !long.contains('=') {
+                pending = cmd.find(long).filter(|o| o.value.is_some());
+            }
+        } else if word.len() > 1 && word.starts_with('-') {
+            // Only a value option ending a bundle of short options takes the next word
+            let last = word.chars().last();
+            pending = cmd.opts.iter()
+                .find(|o| o.short.is_some() && o.short == last && o.value.is_some());
+        } else if free == 0 && !cmd.subs.is_empty() {
+            match cmd.subs.iter().find(|s| s.name == word) {
+                Some(sub) => { cmd = sub; },
+                None => { free += 1; },
+            }
+        } else {
+            free += 1;
+        }
+    }
+    let done: Vec<&str> = done.iter().map(|w| w.as_ref()).collect();
+    let mut out = Vec::new();
+    if let Some(opt) = pending {
+        out = values(opt.hint, &done, cur, "");
+    } else if !dashdash && cur.starts_with("--") && cur.contains('=') {
+        let eq = cur.find('=').unwrap_or(0);
+        if let Some(opt) = cmd.find(&cur[2..eq]) {
+            out = values(opt.hint, &done, &cur[eq + 1..], &cur[..eq + 1]);
+        }
+    } else if !dashdash && cur.starts_with('-') {
+        for opt in cmd.opts.iter().filter(|o| !o.hidden) {
+            out.push(format!("--{}", opt.long));
+            if let Some(c) = opt.short {
+                out.push(format!("-{}", c));
+            }
+        }
+        out.retain(|o| o.starts_with(cur));
+    } else {
+        if free == 0 && !dashdash {
+            out.extend(cmd.subs.iter().map(|s| s.name.to_string())
+                       .filter(|s| s.starts_with(cur)));
+        }
+        out.extend(values(cmd.args_hint, &done, cur, ""));
+    }
+    out
+}
+
+// Candidates for a value of the kind `hint` starting with `cur`, each prefixed
+// by `prefix`, `done` being the words before it
+fn values(hint: Hint, done: &[&str], cur: &str, prefix: &str) -> Vec<String> {
+    let vals = match hint {
+        Hint::Any => { Vec::new() },
+        Hint::File => { paths(cur, false) },
+        Hint::Dir => { paths(cur, true) },
+        Hint::Choices(c) => {
+            c.iter().filter(|v| v.starts_with(cur)).map(|v| v.to_string()).collect()
+        },
+        Hint::Dynamic(f) => { f(done, cur) },
+    };
+    vals.into_iter().map(|v| format!("{}{}", prefix, v)).collect()
+}
+
+// Entries of the directory being typed in `cur`, directories end in a `/`
+fn paths(cur: &str, dirs_only: bool) -> Vec<String> {
+    let (dir, base) = match cur.rfind('/') {
+        Some(i) => { (&cur[..i + 1], &cur[i + 1..]) },
+        None => { ("", cur) },
+    };
+    let read = if dir.is_empty() { Path::new(".") } else { Path::new(dir) };
+    let mut out: Vec<String> = match fs::read_dir(read) {
+        Ok(entries) => {
+            entries.filter_map(|e| e.ok()).filter_map(|e| {
+                let name = e.file_name().to_string_lossy().into_owned();
+                let is_dir = e.path().is_dir();
+                if !name.starts_with(base) || (dirs_only && !is_dir)
+                    || (base.is_empty() && e.path().is_dot()) {
+                    return None;
+                }
+                Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+            }).collect()
+        },
+        Err(_) => { Vec::new() },
+    };
+    out.sort();
+    out
+}
+
+// Turns a command name into a shell function name
+fn ident(name: &str) -> String {
+    name.chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect()
+}
+
+// Every command and its path of names from the root
+fn walk<'a>(cmd: &'a Cmd, path: Vec<&'a str>, out: &mut Vec<(Vec<&'a str>, &'a Cmd)>) {
+    out.push((path.clone(), cmd));
+    for sub in cmd.subs.iter() {
+        let mut p = path.clone();
+        p.push(sub.name);
+        walk(sub, p, out);
+    }
+}
+
+fn visible(cmd: &Cmd) -> Vec<&Opt> {
+    cmd.opts.iter().filter(|o| !o.hidden).collect()
+}
+
+fn single_quote(s: &str) -> String {
+    s.replace('\'', "'\\''")
+}
+
+fn bash(cmd: &Cmd) -> String {
+    let mut cmds = Vec::new();
+    walk(cmd, vec![cmd.name], &mut cmds);
+    let func = ident(cmd.name);
+    let mut s = format!("_{}() {{\n    local cur prev cmd i\n    COMPREPLY=()\n    \
+                         cur=\"${{COMP_WORDS[COMP_CWORD]}}\"\n    \
+                         prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"\n    cmd=\"{}\"\n    \
+                         for ((i = 1; i < COMP_CWORD; i++)); do\n        \
+                         case \"${{cmd}},${{COMP_WORDS[i]}}\" in\n", func, func);
+    for &(ref path, c) in cmds.iter() {
+        for sub in c.subs.iter() {
+            s.push_str(&format!("            {},{}) cmd=\"{}_{}\" ;;\n", ident(&path.join("_")),
+                                sub.name, ident(&path.join("_")), ident(sub.name)));
+        }
+    }
+    s.push_str("        esac\n    done\n    case \"${cmd}\" in\n");
+    for &(ref path, c) in cmds.iter() {
+        s.push_str(&format!("        {})\n            case \"${{prev}}\" in\n",
+                            ident(&path.join("_"))));
+        for opt in visible(c).iter().filter(|o| o.value.is_some()) {
+            let mut names = format!("--{}", opt.long);
+            if let Some(ch) = opt.short { names.push_str(&format!("|-{}", ch)); }
+            let action = match opt.hint {
+                Hint::File => { "compgen -f -- \"${cur}\"".to_string() },
+                Hint::Dir => { "compgen -d -- \"${cur}\"".to_string() },
+                Hint::Choices(ch) => { format!("compgen -W \"{}\" -- \"${{cur}}\"", ch.join(" ")) },
+                _ => { continue; },
+            };
+            s.push_str(&format!("                {}) COMPREPLY=($({})); return ;;\n", names,
+                                action));
+        }
+        s.push_str("            esac\n");
+        let mut words: Vec<String> = Vec::new();
+        for opt in visible(c).iter() {
+            words.push(format!("--{}", opt.long));
+            if let Some(ch) = opt.short { words.push(format!("-{}", ch)); }
+        }
+        let subs: Vec<&str> = c.subs.iter().map(|s| s.name).collect();
+        let args = match c.args_hint {
+            Hint::File => { " $(compgen -f -- \"${cur}\")".to_string() },
+            Hint::Dir => { " $(compgen -d -- \"${cur}\")".to_string() },
+            Hint::Choices(ch) => { format!(" $(compgen -W \"{}\" -- \"${{cur}}\")", ch.join(" ")) },
+            _ => { String::new() },
+        };
+        s.push_str(&format!("            if [[ \"${{cur}}\" == -* ]]; then\n                \
+                             COMPREPLY=($(compgen -W \"{}\" -- \"${{cur}}\"))\n            \
+                             else\n                \
+                             COMPREPLY=($(compgen -W \"{}\" -- \"${{cur}}\"){})\n            \
+                             fi\n            ;;\n", words.join(" "), subs.join(" "), args));
+    }
+    s.push_str(&format!("    esac\n}}\ncomplete -F _{} {}\n", func, cmd.name));
+    s
+}
+
+fn zsh_opt(opt: &Opt) -> String {
+    let help = opt.help.replace('\'', "'\\''").replace('[', "\\[").replace(']', "\\]");
+    let action = match (opt.value, opt.hint) {
+        (None, _) => { String::new() },
+        (Some(v), Hint::File) => { format!(":{}:_files", v) },
+        (Some(v), Hint::Dir) => { format!(":{}:_files -/", v) },
+        (Some(v), Hint::Choices(ch)) => { format!(":{}:({})", v, ch.join(" ")) },
+        (Some(v), _) => { format!(":{}: ", v) },
+    };
+    let eq = if opt.value.is_some() { "=" } else { "" };
+    match opt.short {
+        Some(c) => {
+            format!("'(-{c} --{l})'{{-{c},--{l}{eq}}}'[{h}]{a}'", c = c, l = opt.long, eq = eq,
+                    h = help, a = action)
+        },
+        None => { format!("'--{}{}[{}]{}'", opt.long, eq, help, action) },
+    }
+}
+
+fn zsh(cmd: &Cmd) -> String {
+    let mut cmds = Vec::new();
+    walk(cmd, vec![cmd.name], &mut cmds);
+    let mut s = format!("#compdef {}\n\n", cmd.name);
+    for &(ref path, c) in cmds.iter() {
+        let func = ident(&path.join("_"));
+        s.push_str(&format!("_{}() {{\n    local state\n    _arguments -s \\\n", func));
+        for opt in visible(c).iter() {
+            s.push_str(&format!("        {} \\\n", zsh_opt(opt)));
+        }
+        if c.subs.is_empty() {
+            let action = match c.args_hint {
+                Hint::File => { "_files".to_string() },
+                Hint::Dir => { "_files -/".to_string() },
+                Hint::Choices(ch) => { format!("({})", ch.join(" ")) },
+                _ => { " ".to_string() },
+            };
+            s.push_str(&format!("        '*:arg:{}'\n}}\n\n", action));
+            continue;
+        }
+        let subs: Vec<String> = c.subs.iter().map(|sub| {
+            format!("{}\\:\"{}\"", sub.name, single_quote(&sub.about.replace('"', "\\\"")))
+        }).collect();
+        s.push_str(&format!("        '1:command:(({}))' \\\n        '*::arg:->args'\n    \
+                             case $state in\n        args)\n            \
+                             case $words[1] in\n", subs.join(" ")));
+        for sub in c.subs.iter() {
+            s.push_str(&format!("                {}) _{}_{} ;;\n", sub.name, func,
+                                ident(sub.name)));
+        }
+        s.push_str("            esac\n            ;;\n    esac\n}\n\n");
+    }
+    s.push_str(&format!("compdef _{} {}\n", ident(cmd.name), cmd.name));
+    s
+}
+
+fn fish(cmd: &Cmd) -> String {
+    let mut cmds = Vec::new();
+    walk(cmd, vec![cmd.name], &mut cmds);
+    let mut s = String::new();
+    for &(ref path, c) in cmds.iter() {
+        // Fish conditions only look at the nearest subcommand
+        let cond = if path.len() > 1 {
+            format!(" -n '__fish_seen_subcommand_from {}'", path[path.len() - 1])
+        } else if !c.subs.is_empty() {
+            " -n '__fish_use_subcommand'".to_string()
+        } else {
+            String::new()
+        };
+        for sub in c.subs.iter() {
+            let sub_cond = if path.len() > 1 { cond.clone() }
+                           else { " -n '__fish_use_subcommand'".to_string() };
+            s.push_str(&format!("complete -c {}{} -f -a {} -d '{}'\n", cmd.name, sub_cond,
+                                sub.name, single_quote(sub.about)));
+        }
+        for opt in visible(c).iter() {
+            let mut line = format!("complete -c {}{}", cmd.name, cond);
+            if let Some(ch) = opt.short { line.push_str(&format!(" -s {}", ch)); }
+            line.push_str(&format!(" -l {}", opt.long));
+            if opt.value.is_some() {
+                match opt.hint {
+                    Hint::File => { line.push_str(" -r -F"); },
+                    Hint::Dir => { line.push_str(" -x -a '(__fish_complete_directories)'"); },
+                    Hint::Choices(ch) => { line.push_str(&format!(" -x -a '{}'", ch.join(" "))); },
+                    _ => { line.push_str(" -r"); },
+                }
+            }
+            line.push_str(&format!(" -d '{}'\n", single_quote(opt.help)));
+            s.push_str(&line);
+        }
+    }
+    s
+}
+
+fn powershell(cmd: &Cmd) -> String {
+    let mut cmds = Vec::new();
+    walk(cmd, vec![cmd.name], &mut cmds);
+    let mut s = format!("Register-ArgumentCompleter -Native -CommandName '{}' -ScriptBlock {{\n    \
+                         param($wordToComplete, $commandAst, $cursorPosition)\n    \
+                         $words = @($commandAst.CommandElements | Select-Object -Skip 1 | \
+                         ForEach-Object {{ $_.ToString() }})\n    \
+                         if ($wordToComplete -ne '' -and $words.Count -gt 0) {{ \
+                         $words = $words[0..($words.Count - 2)] }}\n    \
+                         $cmd = '{}'\n    $prev = ''\n    foreach ($w in $words) {{\n        \
+                         switch (\"$cmd,$w\") {{\n", cmd.name, cmd.name);
+    for &(ref path, c) in cmds.iter() {
+        for sub in c.subs.iter() {
+            s.push_str(&format!("            '{},{}' {{ $cmd = '{};{}' }}\n", path.join(";"),
+                                sub.name, path.join(";"), sub.name));
+        }
+    }
+    s.push_str("        }\n        $prev = $w\n    }\n    \
+                $candidates = switch (\"$cmd,$prev\") {\n");
+    for &(ref path, c) in cmds.iter() {
+        for opt in visible(c).iter() {
+            if let Hint::Choices(ch) = opt.hint {
+                let list: Vec<String> = ch.iter().map(|v| format!("'{}'", v)).collect();
+                s.push_str(&format!("        '{},--{}' {{ @({}) }}\n", path.join(";"), opt.long,
+                                    list.join(", ")));
+            }
+        }
+    }
+    s.push_str("        default {\n            switch ($cmd) {\n");
+    for &(ref path, c) in cmds.iter() {
+        let mut words: Vec<String> = Vec::new();
+        for opt in visible(c).iter() {
+            words.push(format!("'--{}'", opt.long));
+            if let Some(ch) = opt.short { words.push(format!("'-{}'", ch)); }
+        }
+        words.extend(c.subs.iter().map(|sub| format!("'{}'", sub.name)));
+        s.push_str(&format!("                '{}' {{ @({}) }}\n", path.join(";"),
+                            words.join(", ")));
+    }
+    s.push_str("            }\n        }\n    }\n    \
+                $candidates | Where-Object { $_ -like \"$wordToComplete*\" } | \
+                ForEach-Object {\n        \
+                [System.Management.Automation.CompletionResult]::new($_, $_, \
+                'ParameterValue', $_)\n    }\n}\n");
+    s
+}
+
+#[test]
+fn test_complete_candidates_options() {
+    let cmd = Cmd::new("util")
+        .opt(Opt::flag("verbose", "More").short('v'))
+        .opt(Opt::value("jobs", "N", "Jobs").short('j'))
+        .opt(Opt::flag("secret", "Hidden").hidden());
+    assert_eq!(candidates(&cmd, &["--v"]), vec!["--verbose"]);
+    assert_eq!(candidates(&cmd, &["-"]), vec!["--verbose", "-v", "--jobs", "-j"]);
+    assert!(candidates(&cmd, &["-vj", ""]).is_empty());
+}
+
+#[test]
+fn test_complete_candidates_dynamic() {
+    fn branches(done: &[&str], cur: &str) -> Vec<String> {
+        let all = if done.contains(&"--remote") { vec!["origin/main"] }
+                  else { vec!["main", "master", "dev"] };
+        all.into_iter().filter(|b| b.starts_with(cur)).map(|b| b.to_string()).collect()
+    }
+    let cmd = Cmd::new("util")
+        .subcommand(Cmd::new("checkout").args_hint(Hint::Dynamic(branches))
+                    .opt(Opt::flag("force", "Force")).opt(Opt::flag("remote", "Remote")));
+    assert_eq!(candidates(&cmd, &["checkout", "ma"]), vec!["main", "master"]);
+    assert_eq!(candidates(&cmd, &["checkout", "--remote", "o"]), vec!["origin/main"]);
+    assert_eq!(candidates(&cmd, &["checkout", "--"]), vec!["--force", "--remote"]);
+    assert_eq!(candidates(&cmd, &[""]), vec!["checkout"]);
+}
+
+#[test]
+fn test_complete_candidates_files() {
+    use utils::test;
+
+    test::create_dir("test-complete");
+    test::create_dir("test-complete/sub");
+    test::create_file("test-complete/file.txt");
+    let cmd = Cmd::new("util").opt(Opt::value("dir", "DIR", "Dir").hint(Hint::Dir))
+        .args_hint(Hint::File);
+    assert_eq!(candidates(&cmd, &["test-complete/"]),
+               vec!["test-complete/file.txt", "test-complete/sub/"]);
+    assert_eq!(candidates(&cmd, &["--dir", "test-complete/"]), vec!["test-complete/sub/"]);
+    test::remove("test-complete");
+}
+
+#[test]
+fn test_complete_generate() {
+    let cmd = Cmd::new("util")
+        .opt(Opt::value("output", "FILE", "Write to FILE").short('o').hint(Hint::File))
+        .subcommand(Cmd::new("add").about("Add things").opt(Opt::flag("force", "Force")));
+    let zsh = generate(Shell::Zsh, &cmd);
+    assert!(zsh.starts_with("#compdef util\n"));
+    assert!(zsh.contains("'(-o --output)'{-o,--output=}'[Write to FILE]:FILE:_files'"));
+    assert!(zsh.contains("add) _util_add ;;"));
+    let fish = generate(Shell::Fish, &cmd);
+    assert!(fish.contains("complete -c util -n '__fish_use_subcommand' -f -a add"));
+    assert!(fish.contains("complete -c util -n '__fish_seen_subcommand_from add' -l force"));
+    let ps = generate(Shell::PowerShell, &cmd);
+    assert!(ps.contains("'util,add' { $cmd = 'util;add' }"));
+    assert!(generate(Shell::Bash, &cmd).contains("util,add) cmd=\"util_add\" ;;"));
+    assert!(generate_dynamic(Shell::Bash, &cmd).contains("util --rpf-complete --"));
+    assert_eq!("pwsh".parse::<Shell>(), Ok(Shell::PowerShell));
+}
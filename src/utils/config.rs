@@ -0,0 +1,353 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+use utils::PathMod;
+use utils::Prog;
+use utils::dirs::{Dir,base_dir};
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self,Read};
+use std::path::{Path,PathBuf};
+use std::str::FromStr;
+
+/// Where a configuration value came from
+#[derive(Clone,Debug,PartialEq)]
+pub enum Source {
+    /// A configuration file and the line the value was set on
+    File(PathBuf, usize),
+    /// An environment variable
+    Env(String),
+    /// A command-line option
+    Arg(String),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Source::File(ref path, line) => { write!(f, "{}:{}", path.as_str(), line) },
+            Source::Env(ref var) => { write!(f, "environment variable {}", var) },
+            Source::Arg(ref opt) => { write!(f, "option '{}'", opt) },
+        }
+    }
+}
+
+/// A configuration value and its source
+#[derive(Clone,Debug,PartialEq)]
+pub struct Value {
+    /// Value as written
+    pub value: String,
+    /// Where the value was set
+    pub source: Source,
+}
+
+/// Error for an unreadable configuration file, a syntax error or a value that
+/// could not be parsed
+#[derive(Debug)]
+pub struct ConfigError {
+    /// Where the error occurred, `None` if a file could not be read
+    pub source: Option<Source>,
+    /// Description of the error
+    pub mesg: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.source {
+            Some(ref source) => { write!(f, "{}: {}", source, self.mesg) },
+            None => { write!(f, "{}", self.mesg) },
+        }
+    }
+}
+
+/// Layered program configuration from files, environment variables and
+/// command-line options
+///
+/// Values merged later take precedence, `Config::load` merges system files,
+/// then the user's file and then environment variables, leaving command-line
+/// options to be merged last with `merge_args`.
+///
+/// Files use an INI-like format of `key = value` lines, with `[section]`
+/// headers prefixing the keys that follow them as `section.key`. Lines
+/// starting with `#` or `;` are comments and values may be double quoted.
+/// Keys are case-insensitive and treat `.`, `-` and `_` as the same, so that
+/// `core.log-level` in a file is set by the environment variable
+/// `UTIL_CORE_LOG_LEVEL`.
+///
+/// # Example
+///
+/// ```
+/// use rpf::Config;
+///
+/// let mut conf = Config::new();
+/// conf.merge_str("jobs = 2\n[color]\nwhen = \"always\"\n", "config").unwrap();
+/// conf.merge_args(vec![("jobs", "4")]);
+/// assert_eq!(conf.get("color.when"), Some("always"));
+/// assert_eq!(conf.parse::<u32>("jobs").unwrap(), Some(4));
+/// ```
+#[derive(Clone,Debug,Default)]
+pub struct Config {
+    values: Vec<(String, Value)>,
+}
+
+impl Config {
+    /// Creates an empty `Config`
+    pub fn new() -> Config {
+        Config { values: Vec::new() }
+    }
+
+    /// Loads configuration for `prog` from its configuration files and from
+    /// environment variables prefixed with its name
+    pub fn load(prog: &Prog) -> Result<Config, ConfigError> {
+        let mut conf = Config::new();
+        for path in Config::paths(prog.name).iter() {
+            if path.is_file() {
+                conf.merge_file(path)?;
+            }
+        }
+        conf.merge_env(&env_prefix(prog.name));
+        Ok(conf)
+    }
+
+    /// Returns the configuration files for a program named `name`, lowest
+    /// precedence first: `/etc/<name>/config`, then `<dir>/<name>/config` for
+    /// each directory in `$XDG_CONFIG_DIRS` and finally
+    /// `$XDG_CONFIG_HOME/<name>/config`, with `$XDG_CONFIG_HOME` defaulting to
+    /// `~/.config`
+    pub fn paths(name: &str) -> Vec<PathBuf> {
+        let mut paths = vec![Path::new("/etc").join(name).join("config")];
+        if let Some(dirs) = env::var_os("XDG_CONFIG_DIRS") {
+            let mut dirs: Vec<PathBuf> = env::split_paths(&dirs)
+                .filter(|p| p.is_absolute()).collect();
+            // Earlier directories in $XDG_CONFIG_DIRS are more important
+            dirs.reverse();
+            for dir in dirs.iter() {
+                paths.push(dir.join(name).join("config"));
+            }
+        }
+        if let Some(home) = base_dir(Dir::Config) {
+            paths.push(home.join(name).join("config"));
+        }
+        paths
+    }
+
+    /// Merges the configuration file at `path`
+    pub fn merge_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ConfigError> {
+        let mut text = String::new();
+        if let Err(e) = fs::File::open(&path).and_then(|mut f| f.read_to_string(&mut text)) {
+            return Err(read_error(path.as_ref(), e));
+        }
+        self.merge_str(&text, path)
+    }
+
+    /// Merges configuration text, `path` is used to report where values and
+    /// errors come from
+    pub fn merge_str<P: AsRef<Path>>(&mut self, text: &str, path: P) -> Result<(), ConfigError> {
+        let mut section = String::new();
+        for (num, line) in text.lines().enumerate() {
+            let source = Source::File(path.as_ref().to_path_buf(), num + 1);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') {
+                if !line.ends_with(']') || line.len() < 3 {
+                    return Err(ConfigError { source: Some(source),
+                                             mesg: "invalid section header".to_string() });
+                }
+                section = line[1..line.len() - 1].trim().to_string();
+                continue;
+            }
+            let (key, val) = match line.find('=') {
+                Some(i) => { (line[..i].trim(), line[i + 1..].trim()) },
+                None => {
+                    return Err(ConfigError { source: Some(source),
+                                             mesg: "expected 'key = value'".to_string() });
+                },
+            };
+            if key.is_empty() {
+                return Err(ConfigError { source: Some(source),
+                                         mesg: "missing key".to_string() });
+            }
+            let val = match unquote(val) {
+                Ok(v) => { v },
+                Err(e) => { return Err(ConfigError { source: Some(source), mesg: e }); },
+            };
+            let key = if section.is_empty() { key.to_string() }
+                      else { format!("{}.{}", section, key) };
+            self.set(&key, Value { value: val, source });
+        }
+        Ok(())
+    }
+
+    /// Merges every environment variable starting with `prefix` followed by an
+    /// underscore, `UTIL_JOBS` sets `jobs` for the prefix `UTIL`
+    pub fn merge_env(&mut self, prefix: &str) {
+        let prefix = format!("{}_", prefix);
+        let mut vars: Vec<(String, String)> = env::vars()
+            .filter(|v| v.0.starts_with(&prefix) && v.0.len() > prefix.len()).collect();
+        vars.sort();
+        for (var, val) in vars.into_iter() {
+            let key = var[prefix.len()..].to_string();
+            self.set(&key, Value { value: val, source: Source::Env(var) });
+        }
+    }
+
+    /// Merges parsed command-line options given as pairs of key and value
+    pub fn merge_args<I, K, V>(&mut self, args: I)
+        where I: IntoIterator<Item=(K, V)>, K: AsRef<str>, V: AsRef<str> {
+        for (key, val) in args {
+            let source = Source::Arg(format!("--{}", key.as_ref()));
+            self.set(key.as_ref(), Value { value: val.as_ref().to_string(), source });
+        }
+    }
+
+    /// Sets `key`, replacing any previous value
+    pub fn set(&mut self, key: &str, value: Value) {
+        let key = normalize(key);
+        match self.values.iter_mut().find(|e| e.0 == key) {
+            Some(entry) => { entry.1 = value; },
+            None => { self.values.push((key, value)); },
+        }
+    }
+
+    /// Returns the value and source for `key`
+    pub fn entry(&self, key: &str) -> Option<&Value> {
+        let key = normalize(key);
+        self.values.iter().find(|e| e.0 == key).map(|e| &e.1)
+    }
+
+    /// Returns the value for `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entry(key).map(|v| &v.value[..])
+    }
+
+    /// Parses the value for `key`, the error names the file and line, the
+    /// environment variable or the option the invalid value came from
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rpf::Config;
+    ///
+    /// let mut conf = Config::new();
+    /// conf.merge_str("\njobs = many\n", "/etc/util/config").unwrap();
+    /// let err = conf.parse::<u32>("jobs").unwrap_err();
+    /// assert_eq!(err.to_string(),
+    ///            "/etc/util/config:2: invalid value 'many' for 'jobs'");
+    /// ```
+    pub fn parse<T: FromStr>(&self, key: &str) -> Result<Option<T>, ConfigError> {
+        match self.entry(key) {
+            Some(v) => {
+                match v.value.parse::<T>() {
+                    Ok(t) => { Ok(Some(t)) },
+                    Err(_) => {
+                        Err(ConfigError {
+                            source: Some(v.source.clone()),
+                            mesg: format!("invalid value '{}' for '{}'", v.value, key),
+                        })
+                    },
+                }
+            },
+            None => { Ok(None) },
+        }
+    }
+
+    /// Returns every key and value, in the order keys were first set
+    pub fn iter(&self) -> ::std::slice::Iter<'_, (String, Value)> {
+        self.values.iter()
+    }
+}
+
+impl Prog {
+    /// Loads configuration for the program, see `Config::load`
+    pub fn config(&self) -> Result<Config, ConfigError> {
+        Config::load(self)
+    }
+}
+
+/// Returns the prefix used for a program's environment variables, the name in
+/// upper case with `-` replaced by `_`
+///
+/// # Example
+///
+/// ```
+/// assert_eq!(rpf::env_prefix("my-util"), "MY_UTIL");
+/// ```
+pub fn env_prefix(name: &str) -> String {
+    name.to_uppercase().replace('-', "_")
+}
+
+fn normalize(key: &str) -> String {
+    key.to_lowercase().replace(['.', '-'], "_")
+}
+
+fn read_error(path: &Path, e: io::Error) -> ConfigError {
+    ConfigError { source: None, mesg: format!("unable to read '{}': {}", path.as_str(), e) }
+}
+
+fn unquote(val: &str) -> Result<String, String> {
+    if !val.starts_with('"') {
+        // Unquoted values may be followed by a comment
+        let end = val.find(" #").unwrap_or(val.len());
+        return Ok(val[..end].trim_end().to_string());
+    }
+    let mut out = String::new();
+    let mut chars = val[1..].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let rest: String = chars.collect();
+                let rest = rest.trim();
+                if rest.is_empty() || rest.starts_with('#') { return Ok(out); }
+                return Err("unexpected text after quoted value".to_string());
+            },
+            '\\' => {
+                match chars.next() {
+                    Some('n') => { out.push('\n'); },
+                    Some('t') => { out.push('\t'); },
+                    Some(c) => { out.push(c); },
+                    None => { break; },
+                }
+            },
+            c => { out.push(c); },
+        }
+    }
+    Err("unterminated quoted value".to_string())
+}
+
+#[test]
+fn test_config_merge_str() {
+    let mut conf = Config::new();
+    conf.merge_str("# comment\nname = util # trailing\n[core]\nlog-level = \"a \\\"b\\\"\"\n",
+                   "config").unwrap();
+    assert_eq!(conf.get("name"), Some("util"));
+    assert_eq!(conf.get("core.log_level"), Some("a \"b\""));
+    assert_eq!(conf.entry("CORE.LOG-LEVEL").unwrap().source,
+               Source::File(PathBuf::from("config"), 4));
+}
+
+#[test]
+fn test_config_syntax_error() {
+    let mut conf = Config::new();
+    let err = conf.merge_str("a = 1\nb\n", "config").unwrap_err();
+    assert_eq!(err.to_string(), "config:2: expected 'key = value'");
+    assert!(conf.merge_str("a = \"open\n", "config").is_err());
+}
+
+#[test]
+fn test_config_precedence() {
+    env::set_var("RPF_CONFIG_TEST_JOBS", "3");
+    env::set_var("RPF_CONFIG_TEST_CORE_COLOR", "never");
+    let mut conf = Config::new();
+    conf.merge_str("jobs = 1\nkeep = yes\n[core]\ncolor = auto\n", "config").unwrap();
+    conf.merge_env("RPF_CONFIG_TEST");
+    assert_eq!(conf.get("jobs"), Some("3"));
+    assert_eq!(conf.get("core.color"), Some("never"));
+    conf.merge_args(vec![("jobs", "x")]);
+    assert_eq!(conf.get("keep"), Some("yes"));
+    assert_eq!(conf.parse::<u32>("jobs").unwrap_err().to_string(),
+               "option '--jobs': invalid value 'x' for 'jobs'");
+}
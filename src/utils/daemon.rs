@@ -0,0 +1,267 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Running in the background as a daemon
+//!
+//! `Prog::daemonize` locks a PID file, by default `<name>.pid` in the
+//! program's runtime directory, and refuses to start if another instance
+//! holds the lock. It then forks twice to detach from the terminal, changes
+//! directory and redirects the standard streams. The PID file is removed by
+//! `Prog::exit`, so a daemon stopped by `SIGTERM` should use
+//! `Prog::handle_signals` and `Prog::check_signals`.
+//!
+//! Forking copies only the calling thread, so daemonize before starting any.
+//!
+//! ```no_run
+//! use rpf::{Cmd,Prog};
+//! use rpf::daemon::{Daemon,FOREGROUND};
+//!
+//! let prog = Prog::new("util", "0.1.0", "2015");
+//! let m = prog.parse_args(&Cmd::new("util").opt(FOREGROUND));
+//! prog.daemonize(&Daemon::new().foreground(m.has(FOREGROUND.long)).stderr("/tmp/util.log"));
+//! prog.handle_signals();
+//! loop {
+//!     prog.check_signals();
+//!     // serve requests
+//! }
+//! ```
+
+extern crate libc;
+
+use utils::{Dir,Exit,ExitStatus,Opt,Prog};
+
+use std::error::Error;
+use std::fmt;
+use std::fs::{self,File,OpenOptions};
+use std::io::{self,Read,Seek,SeekFrom,Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path,PathBuf};
+use std::process;
+
+/// Option that keeps `Prog::daemonize` from detaching from the terminal
+pub static FOREGROUND: Opt = Opt::flag("foreground", "Stay in the foreground, do not detach");
+
+/// Reasons a daemon could not be started
+#[derive(Debug)]
+pub enum DaemonError {
+    /// Another instance holds the lock on the PID file, the PID is `None` if
+    /// it has not been written yet
+    Running(Option<u32>, PathBuf),
+    /// A system call or file operation failed
+    Io(String, io::Error),
+}
+
+impl fmt::Display for DaemonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DaemonError::Running(Some(pid), ref path) => {
+                write!(f, "Already running as PID {} (locked '{}')", pid, path.display())
+            },
+            DaemonError::Running(None, ref path) => {
+                write!(f, "Already running (locked '{}')", path.display())
+            },
+            DaemonError::Io(ref what, ref e) => { write!(f, "{}: {}", what, e) },
+        }
+    }
+}
+
+impl Error for DaemonError {}
+
+/// How `Prog::daemonize` starts the daemon
+#[derive(Clone,Debug)]
+pub struct Daemon {
+    pid_file: Option<PathBuf>,
+    dir: PathBuf,
+    stdout: Option<PathBuf>,
+    stderr: Option<PathBuf>,
+    foreground: bool,
+}
+
+impl Daemon {
+    /// Creates a daemon that runs in `/` with its standard streams on
+    /// `/dev/null` and its PID file in the runtime directory
+    pub fn new() -> Daemon {
+        Daemon { pid_file: None, dir: PathBuf::from("/"), stdout: None, stderr: None,
+                 foreground: false }
+    }
+
+    /// Uses `path` as the PID file
+    pub fn pid_file<P: Into<PathBuf>>(mut self, path: P) -> Daemon {
+        self.pid_file = Some(path.into());
+        self
+    }
+
+    /// Sets the directory the daemon runs in
+    pub fn current_dir<P: Into<PathBuf>>(mut self, dir: P) -> Daemon {
+        self.dir = dir.into();
+        self
+    }
+
+    /// Appends standard output to the file at `path`
+    pub fn stdout<P: Into<PathBuf>>(mut self, path: P) -> Daemon {
+        self.stdout = Some(path.into());
+        self
+    }
+
+    /// Appends standard error to the file at `path`
+    pub fn stderr<P: Into<PathBuf>>(mut self, path: P) -> Daemon {
+        self.stderr = Some(path.into());
+        self
+    }
+
+    /// Stays attached to the terminal, keeping the directory and standard
+    /// streams, while still using the PID file. Useful under service managers
+    /// and for debugging.
+    pub fn foreground(mut self, foreground: bool) -> Daemon {
+        self.foreground = foreground;
+        self
+    }
+}
+
+impl Default for Daemon {
+    fn default() -> Daemon {
+        Daemon::new()
+    }
+}
+
+/// Opens and locks the PID file at `path`, failing with
+/// `DaemonError::Running` if another process holds the lock. The lock lasts
+/// until the returned file is closed.
+pub fn lock_pid_file<P: AsRef<Path>>(path: P) -> Result<File, DaemonError> {
+    let path = path.as_ref();
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false)
+        .mode(0o644).open(path).map_err(fail(&format!("Cannot open '{}'", path.display())))?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::WouldBlock {
+            return Err(fail(&format!("Cannot lock '{}'", path.display()))(e));
+        }
+        let mut text = String::new();
+        let _ = file.read_to_string(&mut text);
+        return Err(DaemonError::Running(text.trim().parse().ok(), path.to_path_buf()));
+    }
+    Ok(file)
+}
+
+// Replaces the contents of the PID file with the current PID
+fn write_pid(file: &mut File) -> io::Result<()> {
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    writeln!(file, "{}", process::id())?;
+    file.sync_all()
+}
+
+// Forks, ending the parent at once without running exit hooks
+fn fork() -> io::Result<()> {
+    match unsafe { libc::fork() } {
+        -1 => { Err(io::Error::last_os_error()) },
+        0 => { Ok(()) },
+        _ => { unsafe { libc::_exit(0) } },
+    }
+}
+
+// Wraps an error with what was being done
+fn fail(what: &str) -> impl FnOnce(io::Error) -> DaemonError {
+    let what = what.to_string();
+    move |e| DaemonError::Io(what, e)
+}
+
+// Points `fd` at the file at `path` or at `/dev/null`
+fn redirect(fd: i32, path: Option<&PathBuf>) -> io::Result<()> {
+    let file = match path {
+        Some(p) => { OpenOptions::new().append(true).create(true).mode(0o640).open(p)? },
+        None => { OpenOptions::new().read(true).write(true).open("/dev/null")? },
+    };
+    if unsafe { libc::dup2(file.as_raw_fd(), fd) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+impl Daemon {
+    // Detaches from the terminal, the calling process exits successfully
+    fn detach(&self) -> Result<(), DaemonError> {
+        fork().map_err(fail("Cannot fork"))?;
+        if unsafe { libc::setsid() } == -1 {
+            return Err(fail("Cannot start a session")(io::Error::last_os_error()));
+        }
+        // The second fork leaves a process that is not a session leader, so
+        // it can never acquire a controlling terminal
+        fork().map_err(fail("Cannot fork"))?;
+        let dir = format!("Cannot change directory to '{}'", self.dir.display());
+        ::std::env::set_current_dir(&self.dir).map_err(fail(&dir))?;
+        redirect(0, None).map_err(fail("Cannot redirect standard input"))?;
+        redirect(1, self.stdout.as_ref()).map_err(fail("Cannot redirect standard output"))?;
+        redirect(2, self.stderr.as_ref()).map_err(fail("Cannot redirect standard error"))?;
+        Ok(())
+    }
+}
+
+impl Prog {
+    /// Starts running as a daemon as set by `daemon`, returning the path of
+    /// the PID file. Exits with `Exit::error` if another instance is running
+    /// or the daemon cannot be started. The PID file is removed by
+    /// `Prog::exit`.
+    pub fn daemonize(&self, daemon: &Daemon) -> Option<PathBuf> {
+        match self.try_daemonize(daemon) {
+            Ok(path) => { Some(path) },
+            Err(e) => { self.error(e.to_string(), ExitStatus::Error); None },
+        }
+    }
+
+    fn try_daemonize(&self, daemon: &Daemon) -> Result<PathBuf, DaemonError> {
+        let path = match daemon.pid_file {
+            Some(ref p) => { p.clone() },
+            None => {
+                self.create_dir(Dir::Runtime)
+                    .map_err(fail("Cannot create runtime directory"))?
+                    .join(format!("{}.pid", self.name))
+            },
+        };
+        // The lock is taken before forking so that a second instance fails
+        // while still attached to the terminal, and is inherited by the daemon
+        let mut file = lock_pid_file(&path)?;
+        if !daemon.foreground {
+            self.io.flush();
+            daemon.detach()?;
+        }
+        write_pid(&mut file).map_err(fail(&format!("Cannot write '{}'", path.display())))?;
+        // The hook owns the file, so the lock is held until the hook has run
+        // and removed it
+        let pid_file = path.clone();
+        self.on_exit.hook(move || {
+            let _ = fs::remove_file(&pid_file);
+            let _ = &file;
+        });
+        Ok(path)
+    }
+}
+
+#[test]
+fn test_daemon_pid_file() {
+    use utils::{ExitStrategy,Output};
+    use utils::test;
+
+    fs::create_dir_all("test-daemon").unwrap();
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    prog.on_exit.set_strategy(ExitStrategy::Return);
+    let daemon = Daemon::new().pid_file("test-daemon/util.pid").foreground(true);
+    let path = prog.daemonize(&daemon).unwrap();
+    let pid = fs::read_to_string(&path).unwrap();
+    assert_eq!(pid, format!("{}\n", process::id()));
+    match lock_pid_file(&path) {
+        Err(DaemonError::Running(Some(p), _)) => { assert_eq!(p, process::id()); },
+        other => { panic!("expected the PID file to be locked, got {:?}", other); },
+    }
+    let second = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    second.on_exit.set_strategy(ExitStrategy::Return);
+    assert!(second.daemonize(&daemon).is_none());
+    assert!(second.io.take_err().contains("Already running as PID"));
+    assert!(path.exists());
+    prog.exit(ExitStatus::Ok);
+    assert!(!path.exists());
+    assert!(lock_pid_file(&path).is_ok());
+    test::remove("test-daemon");
+}
@@ -0,0 +1,193 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+use utils::Prog;
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+#[cfg(target_family = "unix")]
+use std::os::unix::fs::DirBuilderExt;
+use std::path::PathBuf;
+
+/// Kinds of per-user directories from the XDG Base Directory specification
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum Dir {
+    /// `$XDG_CONFIG_HOME`, defaults to `~/.config`
+    Config,
+    /// `$XDG_CACHE_HOME`, defaults to `~/.cache`
+    Cache,
+    /// `$XDG_DATA_HOME`, defaults to `~/.local/share`
+    Data,
+    /// `$XDG_STATE_HOME`, defaults to `~/.local/state`
+    State,
+    /// `$XDG_RUNTIME_DIR`, which has no default
+    Runtime,
+}
+
+impl Dir {
+    fn var(&self) -> &'static str {
+        match *self {
+            Dir::Config => { "XDG_CONFIG_HOME" },
+            Dir::Cache => { "XDG_CACHE_HOME" },
+            Dir::Data => { "XDG_DATA_HOME" },
+            Dir::State => { "XDG_STATE_HOME" },
+            Dir::Runtime => { "XDG_RUNTIME_DIR" },
+        }
+    }
+
+    fn fallback(&self) -> Option<&'static str> {
+        match *self {
+            Dir::Config => { Some(".config") },
+            Dir::Cache => { Some(".cache") },
+            Dir::Data => { Some(".local/share") },
+            Dir::State => { Some(".local/state") },
+            Dir::Runtime => { None },
+        }
+    }
+}
+
+/// Returns the user's home directory from `$HOME`, or `%USERPROFILE%` on
+/// Windows
+pub fn home_dir() -> Option<PathBuf> {
+    home_in(&|var| env::var_os(var))
+}
+
+// Looks up the home directory with `env` in place of the environment
+fn home_in(env: &dyn Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    let var = if cfg!(target_family = "windows") { "USERPROFILE" } else { "HOME" };
+    match env(var) {
+        Some(ref home) if !home.is_empty() => { Some(PathBuf::from(home)) },
+        _ => { None },
+    }
+}
+
+/// Returns the base directory of a kind, shared by all programs. Relative
+/// paths in the environment are ignored as the specification requires.
+///
+/// # Example
+///
+/// ```
+/// use rpf::{Dir,base_dir};
+/// use std::path::PathBuf;
+///
+/// std::env::set_var("XDG_CACHE_HOME", "/tmp/cache");
+/// assert_eq!(base_dir(Dir::Cache), Some(PathBuf::from("/tmp/cache")));
+/// ```
+pub fn base_dir(kind: Dir) -> Option<PathBuf> {
+    base_dir_in(kind, &|var| env::var_os(var))
+}
+
+// Looks up a base directory with `env` in place of the environment, so tests
+// need not change the process's environment
+fn base_dir_in(kind: Dir, env: &dyn Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    if let Some(dir) = env(kind.var()) {
+        let dir = PathBuf::from(dir);
+        if dir.is_absolute() { return Some(dir); }
+    }
+    match kind.fallback() {
+        Some(rel) => { home_in(env).map(|h| h.join(rel)) },
+        None => { None },
+    }
+}
+
+impl Prog {
+    /// Returns the program's directory of a kind, `<base>/<name>`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rpf::{Prog,Dir,PathMod};
+    ///
+    /// std::env::set_var("XDG_DATA_HOME", "/home/user/.local/share");
+    /// let prog = Prog::new("util", "0.1.0", "2015");
+    /// let data = prog.dir(Dir::Data).unwrap();
+    /// assert_eq!(data.as_str(), "/home/user/.local/share/util");
+    /// ```
+    pub fn dir(&self, kind: Dir) -> Option<PathBuf> {
+        base_dir(kind).map(|d| d.join(self.name))
+    }
+
+    /// Returns the program's configuration directory
+    pub fn config_dir(&self) -> Option<PathBuf> {
+        self.dir(Dir::Config)
+    }
+
+    /// Returns the program's cache directory
+    pub fn cache_dir(&self) -> Option<PathBuf> {
+        self.dir(Dir::Cache)
+    }
+
+    /// Returns the program's data directory
+    pub fn data_dir(&self) -> Option<PathBuf> {
+        self.dir(Dir::Data)
+    }
+
+    /// Returns the program's state directory
+    pub fn state_dir(&self) -> Option<PathBuf> {
+        self.dir(Dir::State)
+    }
+
+    /// Returns the program's runtime directory, `None` if `$XDG_RUNTIME_DIR` is
+    /// not set
+    pub fn runtime_dir(&self) -> Option<PathBuf> {
+        self.dir(Dir::Runtime)
+    }
+
+    /// Returns the program's directory of a kind, creating it and any missing
+    /// parents if needed. Directories are created readable only by the user.
+    pub fn create_dir(&self, kind: Dir) -> io::Result<PathBuf> {
+        self.create_dir_in(kind, base_dir(kind))
+    }
+
+    // Creates the program's directory under `base`, the base directory of
+    // `kind`
+    fn create_dir_in(&self, kind: Dir, base: Option<PathBuf>) -> io::Result<PathBuf> {
+        let dir = match base.map(|d| d.join(self.name)) {
+            Some(d) => { d },
+            None => {
+                return Err(io::Error::new(io::ErrorKind::NotFound,
+                                          format!("{} is not set", kind.var())));
+            },
+        };
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(target_family = "unix")]
+        builder.mode(0o700);
+        builder.create(&dir)?;
+        Ok(dir)
+    }
+}
+
+#[test]
+fn test_dirs_relative_ignored() {
+    let env = |var: &str| match var {
+        "XDG_STATE_HOME" => { Some(OsString::from("relative/state")) },
+        "HOME" | "USERPROFILE" => { Some(OsString::from("/home/user")) },
+        _ => { None },
+    };
+    assert_eq!(base_dir_in(Dir::State, &env), Some(PathBuf::from("/home/user/.local/state")));
+    assert_eq!(base_dir_in(Dir::State, &|_| None), None);
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn test_dirs_create() {
+    use std::os::unix::fs::PermissionsExt;
+    use utils::test;
+
+    let runtime = env::current_dir().unwrap().join("test-runtime");
+    let env = |var: &str| match var {
+        "XDG_RUNTIME_DIR" => { Some(runtime.clone().into_os_string()) },
+        _ => { None },
+    };
+    let prog = Prog::new("util", "0.1.0", "2015");
+    assert!(prog.create_dir_in(Dir::Runtime, base_dir_in(Dir::Runtime, &|_| None)).is_err());
+    let dir = prog.create_dir_in(Dir::Runtime, base_dir_in(Dir::Runtime, &env)).unwrap();
+    assert_eq!(dir, runtime.join("util"));
+    let mode = fs::metadata(&dir).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o700);
+    test::remove("test-runtime");
+}
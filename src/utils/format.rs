@@ -0,0 +1,409 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Human and machine-readable output of records
+//!
+//! A command emits each record once through `Records` and the format picked
+//! with `--format` decides how it is printed: a table for people, or JSON, JSON
+//! lines, CSV or TSV for scripts. In the machine formats escape codes are
+//! removed from values, `Prog::set_format` disables colour in the program's
+//! `Output`, and errors
+//! reported through `Exit` are printed as JSON objects by the JSON formats.
+//!
+//! ```
+//! use rpf::{Output,Prog};
+//! use rpf::format::Format;
+//!
+//! let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+//! let mut out = prog.records(Format::Csv, &["name", "size"]);
+//! out.record(vec!["a, b".into(), 12.into()]);
+//! out.finish();
+//! assert_eq!(prog.io.take_out(), "name,size\n\"a, b\",12\n");
+//! ```
+
+use utils::{Opt,Prog};
+use utils::table::{Align,Table};
+use utils::text;
+use utils::value;
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize,Ordering};
+
+/// Option selecting the output format, read with `Prog::select_format`
+pub static FORMAT: Opt = Opt::value("format", "FORMAT",
+                                    "Output format: human, json, jsonl, csv or tsv")
+    .choices(&["human", "json", "jsonl", "csv", "tsv"]);
+
+/// An output format
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum Format {
+    /// Aligned columns
+    Human,
+    /// A JSON array of objects
+    Json,
+    /// One JSON object per line
+    Jsonl,
+    /// Comma-separated values with a header line
+    Csv,
+    /// Tab-separated values with a header line
+    Tsv,
+}
+
+const FORMATS: [Format; 5] = [Format::Human, Format::Json, Format::Jsonl, Format::Csv,
+                              Format::Tsv];
+
+impl Format {
+    /// Returns true for every format but `Human`
+    pub fn is_machine(&self) -> bool {
+        *self != Format::Human
+    }
+
+    /// Returns true for `Json` and `Jsonl`
+    pub fn is_json(&self) -> bool {
+        *self == Format::Json || *self == Format::Jsonl
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            Format::Human => { "human" },
+            Format::Json => { "json" },
+            Format::Jsonl => { "jsonl" },
+            Format::Csv => { "csv" },
+            Format::Tsv => { "tsv" },
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match FORMATS.iter().find(|f| f.name() == s) {
+            Some(f) => { Ok(*f) },
+            None => { Err("expected one of human, json, jsonl, csv, tsv".to_string()) },
+        }
+    }
+}
+
+/// The format a `Prog` prints records and errors in
+pub struct CurrentFormat(AtomicUsize);
+
+impl CurrentFormat {
+    /// Creates a setting holding `Format::Human`
+    pub const fn new() -> CurrentFormat {
+        CurrentFormat(AtomicUsize::new(0))
+    }
+
+    /// Returns the format
+    pub fn get(&self) -> Format {
+        FORMATS[self.0.load(Ordering::SeqCst)]
+    }
+
+    /// Changes the format without changing whether colour is used, see
+    /// `Prog::set_format`
+    pub fn set(&self, format: Format) {
+        let i = FORMATS.iter().position(|f| *f == format).unwrap_or(0);
+        self.0.store(i, Ordering::SeqCst);
+    }
+}
+
+impl Default for CurrentFormat {
+    fn default() -> CurrentFormat {
+        CurrentFormat::new()
+    }
+}
+
+/// A field of a record
+#[derive(Clone,Debug,PartialEq)]
+pub enum Value {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Null,
+}
+
+impl Value {
+    // The value as text, without escape codes when `plain` is set
+    fn text(&self, plain: bool) -> String {
+        match *self {
+            Value::Str(ref s) if plain => { text::strip_ansi(s) },
+            Value::Str(ref s) => { s.clone() },
+            Value::Int(i) => { i.to_string() },
+            Value::Float(f) => { f.to_string() },
+            Value::Bool(b) => { b.to_string() },
+            Value::Null => { String::new() },
+        }
+    }
+
+    fn json(&self) -> String {
+        match *self {
+            Value::Str(ref s) => { json_string(&text::strip_ansi(s)) },
+            Value::Float(f) if !f.is_finite() => { "null".to_string() },
+            Value::Null => { "null".to_string() },
+            _ => { self.text(true) },
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Value {
+    fn from(s: &'a str) -> Value { Value::Str(s.to_string()) }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value { Value::Str(s) }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Value { Value::Int(i) }
+}
+
+impl From<i32> for Value {
+    fn from(i: i32) -> Value { Value::Int(i as i64) }
+}
+
+impl From<u32> for Value {
+    fn from(i: u32) -> Value { Value::Int(i as i64) }
+}
+
+impl From<f64> for Value {
+    fn from(f: f64) -> Value { Value::Float(f) }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value { Value::Bool(b) }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Value { v.map(Into::into).unwrap_or(Value::Null) }
+}
+
+/// Prints records in a `Format`, created with `Prog::records`. Human and JSON
+/// output is printed by `finish`, or when dropped, the other formats are
+/// printed as records arrive.
+pub struct Records<'a> {
+    prog: &'a Prog,
+    format: Format,
+    fields: Vec<String>,
+    rows: Vec<Vec<Value>>,
+    count: usize,
+    done: bool,
+}
+
+impl<'a> Records<'a> {
+    /// Adds a record with a value for each field
+    pub fn record(&mut self, values: Vec<Value>) {
+        match self.format {
+            Format::Human | Format::Json => { self.rows.push(values); },
+            Format::Jsonl => { self.print(&format!("{}\n", self.object(&values))); },
+            Format::Csv | Format::Tsv => {
+                if self.count == 0 { self.header(); }
+                self.print(&self.line(&values));
+            },
+        }
+        self.count += 1;
+    }
+
+    fn header(&self) {
+        let header: Vec<Value> = self.fields.iter().map(|f| f[..].into()).collect();
+        self.print(&self.line(&header));
+    }
+
+    /// Prints records that have been held back
+    pub fn finish(mut self) {
+        self.flush();
+    }
+
+    fn flush(&mut self) {
+        if self.done { return; }
+        self.done = true;
+        match self.format {
+            Format::Human => {
+                let mut table = Table::new().header(&self.fields);
+                for i in 0..self.fields.len() {
+                    if self.numeric(i) {
+                        table = table.align(i, Align::Right);
+                    }
+                }
+                for row in self.rows.iter() {
+                    let cells: Vec<String> = row.iter().map(|v| v.text(false)).collect();
+                    table.push(&cells);
+                }
+                self.prog.table(&table);
+            },
+            Format::Json => {
+                let objects: Vec<String> = self.rows.iter().map(|r| self.object(r)).collect();
+                if objects.is_empty() { self.print("[]\n"); }
+                else { self.print(&format!("[\n  {}\n]\n", objects.join(",\n  "))); }
+            },
+            Format::Csv | Format::Tsv if self.count == 0 => { self.header(); },
+            _ => {},
+        }
+    }
+
+    // Returns true if column `i` holds numbers, ignoring missing values
+    fn numeric(&self, i: usize) -> bool {
+        let mut cells = self.rows.iter().filter_map(|r| r.get(i))
+            .filter(|v| **v != Value::Null).peekable();
+        cells.peek().is_some() && cells.all(|v| matches!(*v, Value::Int(_) | Value::Float(_)))
+    }
+
+    fn object(&self, values: &[Value]) -> String {
+        let fields: Vec<String> = self.fields.iter().zip(values.iter())
+            .map(|(k, v)| format!("{}:{}", json_string(k), v.json())).collect();
+        format!("{{{}}}", fields.join(","))
+    }
+
+    fn line(&self, values: &[Value]) -> String {
+        let cells: Vec<String> = values.iter().map(|v| {
+            if self.format == Format::Csv { csv_field(&v.text(true)) }
+            else { tsv_field(&v.text(true)) }
+        }).collect();
+        let sep = if self.format == Format::Csv { "," } else { "\t" };
+        format!("{}\n", cells.join(sep))
+    }
+
+    fn print(&self, text: &str) {
+        self.prog.io.print(format_args!("{}", text));
+    }
+}
+
+impl<'a> Drop for Records<'a> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Quotes a string for JSON
+///
+/// # Example
+/// ```
+/// use rpf::format;
+///
+/// assert_eq!(format::json_string("a \"b\"\n\u{1}"), r#""a \"b\"\n\u0001""#);
+/// ```
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => { out.push_str("\\\""); },
+            '\\' => { out.push_str("\\\\"); },
+            '\n' => { out.push_str("\\n"); },
+            '\r' => { out.push_str("\\r"); },
+            '\t' => { out.push_str("\\t"); },
+            c if (c as u32) < 0x20 => { out.push_str(&format!("\\u{:04x}", c as u32)); },
+            c => { out.push(c); },
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Quotes a CSV field containing separators, quotes or line breaks
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+// Escapes tabs, line breaks and backslashes in a TSV field
+fn tsv_field(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+impl Prog {
+    /// Starts printing records with `fields` in `format`
+    pub fn records(&self, format: Format, fields: &[&str]) -> Records<'_> {
+        Records { prog: self, format, fields: fields.iter().map(|f| f.to_string()).collect(),
+                  rows: Vec::new(), count: 0, done: false }
+    }
+
+    /// Sets the format errors are printed in, disabling colour in `io` for
+    /// machine formats. Other `Prog`s and `Styled` are not affected.
+    pub fn set_format(&self, format: Format) {
+        self.format.set(format);
+        self.io.set_color(!format.is_machine());
+    }
+
+    /// Sets the format from `FORMAT` if it was given, exiting with
+    /// `ExitStatus::OptError` if it is invalid, and returns it
+    pub fn select_format(&self, m: &::utils::Matches) -> Format {
+        if let Some(f) = self.value(m, FORMAT.long, value::parsed::<Format>) {
+            self.set_format(f);
+        }
+        self.format.get()
+    }
+
+    /// Prints `mesg` and `status` as a JSON object on the error stream
+    pub(crate) fn json_error(&self, mesg: &str, code: i32) {
+        self.io.eprint(format_args!("{{\"error\":{},\"status\":{}}}\n",
+                                    json_string(&text::strip_ansi(mesg)), code));
+    }
+}
+
+#[test]
+fn test_format_json() {
+    use utils::Output;
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    let mut out = prog.records(Format::Json, &["name", "ok"]);
+    out.record(vec!["\x1b[1ma\"b\x1b[0m".into(), true.into()]);
+    out.record(vec![Value::Null, f64::NAN.into()]);
+    drop(out);
+    assert_eq!(prog.io.take_out(), "[\n  {\"name\":\"a\\\"b\",\"ok\":true},\n  \
+                                    {\"name\":null,\"ok\":null}\n]\n");
+    let mut out = prog.records(Format::Jsonl, &["n"]);
+    out.record(vec![1.into()]);
+    out.record(vec![2.into()]);
+    out.finish();
+    assert_eq!(prog.io.take_out(), "{\"n\":1}\n{\"n\":2}\n");
+}
+
+#[test]
+fn test_format_tsv_and_human() {
+    use utils::Output;
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    let mut out = prog.records(Format::Tsv, &["a", "b"]);
+    out.record(vec!["x\ty".into(), "1\\2".into()]);
+    out.finish();
+    assert_eq!(prog.io.take_out(), "a\tb\nx\\ty\t1\\\\2\n");
+    let mut out = prog.records(Format::Human, &["NAME", "SIZE"]);
+    out.record(vec!["a".into(), 5.into()]);
+    out.record(vec!["bb".into(), 100.into()]);
+    out.finish();
+    assert_eq!(prog.io.take_out(), "NAME  SIZE\na        5\nbb     100\n");
+    let mut out = prog.records(Format::Human, &["NAME", "SIZE"]);
+    out.record(vec!["a".into(), Value::Null]);
+    out.record(vec!["bb".into(), 100.into()]);
+    out.finish();
+    assert!(prog.io.take_out().ends_with("bb     100\n"));
+}
+
+#[test]
+fn test_format_error() {
+    use utils::{Exit,ExitStatus,ExitStrategy,Output,Styled};
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    prog.on_exit.set_strategy(ExitStrategy::Return);
+    prog.format.set(Format::Json);
+    prog.error("no \"such\" file", ExitStatus::ArgError);
+    assert_eq!(prog.io.take_err(), "{\"error\":\"no \\\"such\\\" file\",\"status\":3}\n");
+    let other = Prog { io: Output::capture(), ..Prog::new("other", "0.1.0", "2015") };
+    prog.set_format(Format::Csv);
+    prog.io.eprint(format_args!("{}\n", "warning".bold()));
+    other.io.eprint(format_args!("{}\n", "warning".bold()));
+    assert_eq!(prog.io.take_err(), "warning\n");
+    assert_eq!(other.io.take_err(), format!("{}\n", "warning".bold()));
+}
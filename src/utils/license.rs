@@ -0,0 +1,241 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+use utils::Prog;
+
+/// A license known to rpf
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub struct License {
+    /// SPDX identifier
+    pub spdx: &'static str,
+    /// Short name used in `--version` output, such as `GPLv3+`
+    pub short: &'static str,
+    /// Full name of the license
+    pub name: &'static str,
+    /// Where the license text can be read
+    pub url: &'static str,
+}
+
+/// Licenses with short notices, keyed by SPDX identifier
+pub static LICENSES: &[License] = &[
+    License { spdx: "AGPL-3.0-only", short: "AGPLv3", name: "GNU AGPL version 3",
+              url: "https://gnu.org/licenses/agpl.html" },
+    License { spdx: "AGPL-3.0-or-later", short: "AGPLv3+", name: "GNU AGPL version 3 or later",
+              url: "https://gnu.org/licenses/agpl.html" },
+    License { spdx: "Apache-2.0", short: "Apache-2.0", name: "Apache License 2.0",
+              url: "https://www.apache.org/licenses/LICENSE-2.0" },
+    License { spdx: "BSD-2-Clause", short: "BSD-2-Clause",
+              name: "BSD 2-Clause \"Simplified\" License",
+              url: "https://opensource.org/licenses/BSD-2-Clause" },
+    License { spdx: "BSD-3-Clause", short: "BSD-3-Clause",
+              name: "BSD 3-Clause \"New\" or \"Revised\" License",
+              url: "https://opensource.org/licenses/BSD-3-Clause" },
+    License { spdx: "GPL-2.0-only", short: "GPLv2", name: "GNU GPL version 2",
+              url: "https://gnu.org/licenses/old-licenses/gpl-2.0.html" },
+    License { spdx: "GPL-2.0-or-later", short: "GPLv2+", name: "GNU GPL version 2 or later",
+              url: "https://gnu.org/licenses/old-licenses/gpl-2.0.html" },
+    License { spdx: "GPL-3.0-only", short: "GPLv3", name: "GNU GPL version 3",
+              url: "https://gnu.org/licenses/gpl.html" },
+    License { spdx: "GPL-3.0-or-later", short: "GPLv3+", name: "GNU GPL version 3 or later",
+              url: "https://gnu.org/licenses/gpl.html" },
+    License { spdx: "ISC", short: "ISC", name: "ISC License",
+              url: "https://opensource.org/licenses/ISC" },
+    License { spdx: "LGPL-2.1-only", short: "LGPLv2.1", name: "GNU LGPL version 2.1",
+              url: "https://gnu.org/licenses/old-licenses/lgpl-2.1.html" },
+    License { spdx: "LGPL-2.1-or-later", short: "LGPLv2.1+",
+              name: "GNU LGPL version 2.1 or later",
+              url: "https://gnu.org/licenses/old-licenses/lgpl-2.1.html" },
+    License { spdx: "LGPL-3.0-only", short: "LGPLv3", name: "GNU LGPL version 3",
+              url: "https://gnu.org/licenses/lgpl.html" },
+    License { spdx: "LGPL-3.0-or-later", short: "LGPLv3+", name: "GNU LGPL version 3 or later",
+              url: "https://gnu.org/licenses/lgpl.html" },
+    License { spdx: "MIT", short: "MIT", name: "MIT License",
+              url: "https://opensource.org/licenses/MIT" },
+    License { spdx: "MPL-2.0", short: "MPL-2.0", name: "Mozilla Public License 2.0",
+              url: "https://mozilla.org/MPL/2.0/" },
+    License { spdx: "Unlicense", short: "Unlicense", name: "The Unlicense",
+              url: "https://unlicense.org" },
+];
+
+const FREE: &str = "This is free software: you are free to change and redistribute it.\n\
+                    There is NO WARRANTY, to the extent permitted by law.\n";
+
+/// Looks up a license by SPDX identifier, ignoring case. The deprecated GNU
+/// identifiers such as `GPL-3.0+`, `LGPL-2.1` and `AGPL-3.0` are also
+/// accepted.
+///
+/// # Example
+/// ```
+/// use rpf::license;
+///
+/// assert_eq!(license::find("gpl-3.0+").unwrap().short, "GPLv3+");
+/// assert!(license::find("Proprietary").is_none());
+/// ```
+pub fn find(spdx: &str) -> Option<&'static License> {
+    let spdx = spdx.trim();
+    // The GNU licenses once had plain version numbers, with `+` for "or later"
+    let lower = spdx.to_lowercase();
+    let gnu = ["gpl-", "lgpl-", "agpl-"].iter().any(|f| lower.starts_with(f))
+        && spdx.matches('-').count() == 1;
+    let spdx = match spdx.strip_suffix('+') {
+        Some(base) if gnu => { format!("{}-or-later", base) },
+        None if gnu => { format!("{}-only", spdx) },
+        _ => { spdx.to_string() },
+    };
+    LICENSES.iter().find(|l| l.spdx.eq_ignore_ascii_case(&spdx))
+}
+
+/// Returns the short license notice for an SPDX expression, `None` if any
+/// license in it is unknown. Expressions of the form `A OR B` list each
+/// license.
+///
+/// # Example
+/// ```
+/// use rpf::license;
+///
+/// assert_eq!(license::notice("GPL-3.0-or-later").unwrap(),
+///            "License GPLv3+: GNU GPL version 3 or later <https://gnu.org/licenses/gpl.html>.\n\
+///             This is free software: you are free to change and redistribute it.\n\
+///             There is NO WARRANTY, to the extent permitted by law.\n");
+/// ```
+pub fn notice(spdx: &str) -> Option<String> {
+    let ids: Vec<&str> = spdx.split(" OR ").flat_map(|s| s.split('/')).collect();
+    let mut found = Vec::new();
+    for id in ids.iter() {
+        found.push(find(id.trim_matches(|c| c == '(' || c == ')'))?);
+    }
+    let mut text = match found.len() {
+        0 => { return None; },
+        1 => { format!("License {}: {} <{}>.\n", found[0].short, found[0].name, found[0].url) },
+        _ => {
+            let mut text = "Licensed under any of:\n".to_string();
+            for l in found.iter() {
+                text.push_str(&format!("  {} <{}>\n", l.name, l.url));
+            }
+            text
+        },
+    };
+    text.push_str(FREE);
+    Some(text)
+}
+
+/// Returns a copyright year range, a single year if both are equal or `to` is
+/// unknown
+///
+/// # Example
+/// ```
+/// use rpf::license;
+///
+/// assert_eq!(license::years("2015", "2017"), "2015-2017");
+/// assert_eq!(license::years("2015", ""), "2015");
+/// ```
+pub fn years(from: &str, to: &str) -> String {
+    match (from.is_empty(), to.is_empty()) {
+        (true, _) => { to.to_string() },
+        (false, true) => { from.to_string() },
+        (false, false) if from == to || to < from => { from.to_string() },
+        (false, false) => { format!("{}-{}", from, to) },
+    }
+}
+
+/// Joins names into an English list, "A", "A and B" or "A, B, and C"
+///
+/// # Example
+/// ```
+/// use rpf::license;
+///
+/// assert_eq!(license::join_names(&["A", "B", "C"]), "A, B, and C");
+/// ```
+pub fn join_names<T: AsRef<str>>(names: &[T]) -> String {
+    match names.len() {
+        0 => { String::new() },
+        1 => { names[0].as_ref().to_string() },
+        2 => { format!("{} and {}", names[0].as_ref(), names[1].as_ref()) },
+        n => {
+            let head: Vec<&str> = names[..n - 1].iter().map(|s| s.as_ref()).collect();
+            format!("{}, and {}", head.join(", "), names[n - 1].as_ref())
+        },
+    }
+}
+
+// Strips an email address from an author, "A <a@example.com>" becomes "A"
+fn author_name(author: &str) -> &str {
+    match author.find('<') {
+        Some(i) if i > 0 => { author[..i].trim() },
+        _ => { author.trim() },
+    }
+}
+
+impl Prog {
+    /// Returns the copyright line and license notice for the program, using
+    /// the license catalogue for `license` and the years from `yr` to the
+    /// build year
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::Prog;
+    ///
+    /// let prog = Prog { license: "MIT", authors: "A <a@example.com>:B",
+    ///                   ..Prog::new("util", "0.1.0", "2015") };
+    /// assert_eq!(prog.copyright_notice(),
+    ///            "Copyright (C) 2015 A and B\n\
+    ///             License MIT: MIT License <https://opensource.org/licenses/MIT>.\n\
+    ///             This is free software: you are free to change and redistribute it.\n\
+    ///             There is NO WARRANTY, to the extent permitted by law.\n");
+    /// ```
+    pub fn copyright_notice(&self) -> String {
+        let names: Vec<&str> = self.authors().into_iter().map(author_name).collect();
+        let holder = if names.is_empty() { format!("{} developers", self.name) }
+                     else { join_names(&names) };
+        let years = years(self.yr, self.build.year().unwrap_or(""));
+        let mut text = if years.is_empty() { format!("Copyright (C) {}\n", holder) }
+                       else { format!("Copyright (C) {} {}\n", years, holder) };
+        match notice(self.license) {
+            Some(n) => { text.push_str(&n); },
+            None if !self.license.is_empty() => {
+                text.push_str(&format!("License {}.\n", self.license));
+            },
+            None => {},
+        }
+        text
+    }
+}
+
+#[test]
+fn test_license_find() {
+    assert_eq!(find("BSD-3-Clause").unwrap().spdx, "BSD-3-Clause");
+    assert_eq!(find("GPL-2.0").unwrap().spdx, "GPL-2.0-only");
+    assert_eq!(find("GPL-2.0+").unwrap().spdx, "GPL-2.0-or-later");
+    assert_eq!(find("AGPL-3.0-only").unwrap().short, "AGPLv3");
+    assert_eq!(find("AGPL-3.0").unwrap().spdx, "AGPL-3.0-only");
+    assert_eq!(find("lgpl-2.1").unwrap().spdx, "LGPL-2.1-only");
+    assert_eq!(find("LGPL-3.0+").unwrap().spdx, "LGPL-3.0-or-later");
+    assert!(find("MIT+").is_none());
+}
+
+#[test]
+fn test_license_notice_dual() {
+    let text = notice("MIT OR Apache-2.0").unwrap();
+    assert!(text.starts_with("Licensed under any of:\n  MIT License"));
+    assert!(notice("MIT OR Proprietary").is_none());
+}
+
+#[test]
+fn test_license_join_names() {
+    let none: [&str; 0] = [];
+    assert_eq!(join_names(&none), "");
+    assert_eq!(join_names(&["A"]), "A");
+    assert_eq!(join_names(&["A", "B"]), "A and B");
+}
+
+#[test]
+fn test_license_copyright_notice_years() {
+    use utils::build::Build;
+
+    let prog = Prog { license: "BSD-3-Clause",
+                      build: Build { date: "2017-01-01", ..Build::empty() },
+                      ..Prog::new("util", "0.1.0", "2015") };
+    assert!(prog.copyright_notice().starts_with("Copyright (C) 2015-2017 util developers\n\
+                                                 License BSD-3-Clause: "));
+}
@@ -0,0 +1,367 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Leveled logging to standard error and a log file
+//!
+//! Messages are logged with the `rpf_log!` macro, which records the module they
+//! come from. Which messages are shown is set by `Prog::init_log` from the
+//! `-v` and `-q` flags and the `<NAME>_LOG` environment variable, which holds
+//! a default level and levels for modules such as `info,util::net=trace`.
+//!
+//! ```
+//! #[macro_use] extern crate rpf;
+//! use rpf::{Output,Prog,text};
+//! use rpf::log::Level;
+//!
+//! fn main() {
+//!     let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+//!     prog.logger.set_level(Level::Info);
+//!     rpf_log!(prog, Info, "copied {} files", 3);
+//!     rpf_log!(prog, Debug, "not shown");
+//!     assert_eq!(text::strip_ansi(&prog.io.take_err()), "util: info: copied 3 files\n");
+//! }
+//! ```
+
+use utils::{Color,Dir,Exit,ExitStatus,Matches,Opt,Prog,Styled};
+use utils::build;
+use utils::config::env_prefix;
+use utils::format::json_string;
+
+use std::env;
+use std::fmt;
+use std::fs::{self,File,OpenOptions};
+use std::io::{self,Write};
+use std::path::{Path,PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex,MutexGuard};
+use std::time::{SystemTime,UNIX_EPOCH};
+
+/// Option raising the log level, may be repeated
+pub static VERBOSE: Opt = Opt::flag("verbose", "Print more messages, repeat for more")
+    .short('v');
+
+/// Option lowering the log level, may be repeated
+pub static QUIET: Opt = Opt::flag("quiet", "Print fewer messages, repeat for fewer").short('q');
+
+/// Size a log file may grow to before it is rotated
+pub const MAX_FILE_SIZE: u64 = 1 << 20;
+
+/// Number of rotated log files kept
+pub const KEEP_FILES: usize = 3;
+
+/// Importance of a message, `Off` shows no messages
+#[derive(Copy,Clone,Debug,PartialEq,Eq,PartialOrd,Ord)]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+const LEVELS: [Level; 6] = [Level::Off, Level::Error, Level::Warn, Level::Info, Level::Debug,
+                            Level::Trace];
+
+impl Level {
+    fn name(&self) -> &'static str {
+        match *self {
+            Level::Off => { "off" },
+            Level::Error => { "error" },
+            Level::Warn => { "warning" },
+            Level::Info => { "info" },
+            Level::Debug => { "debug" },
+            Level::Trace => { "trace" },
+        }
+    }
+
+    fn color(&self) -> Color {
+        match *self {
+            Level::Off | Level::Error => { Color::Red },
+            Level::Warn => { Color::Yellow },
+            Level::Info => { Color::Green },
+            Level::Debug => { Color::Blue },
+            Level::Trace => { Color::Purple },
+        }
+    }
+
+    // Moves the level by `n` steps, staying between `Off` and `Trace`
+    fn shift(self, n: isize) -> Level {
+        let i = (self as isize + n).clamp(0, LEVELS.len() as isize - 1);
+        LEVELS[i as usize]
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Level, String> {
+        match &s.trim().to_lowercase()[..] {
+            "warn" => { Ok(Level::Warn) },
+            s => {
+                LEVELS.iter().find(|l| l.name() == s).cloned()
+                    .ok_or_else(|| format!("unknown log level '{}'", s))
+            },
+        }
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max: u64,
+    keep: usize,
+}
+
+impl LogFile {
+    fn write_line(&mut self, line: &str) {
+        if self.size + line.len() as u64 > self.max && self.size > 0 {
+            let _ = self.rotate();
+        }
+        if self.file.write_all(line.as_bytes()).is_ok() {
+            self.size += line.len() as u64;
+        }
+    }
+
+    // Renames `name.log` to `name.log.1`, `name.log.1` to `name.log.2` and so
+    // on, removing the oldest, and starts a new file
+    fn rotate(&mut self) -> io::Result<()> {
+        let numbered = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        let _ = fs::remove_file(numbered(self.keep));
+        for n in (1..self.keep).rev() {
+            let _ = fs::rename(numbered(n), numbered(n + 1));
+        }
+        if self.keep > 0 {
+            fs::rename(&self.path, numbered(1))?;
+        }
+        self.file = File::create(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+struct State {
+    level: Level,
+    modules: Vec<(String, Level)>,
+    timestamps: bool,
+    file: Option<LogFile>,
+}
+
+/// Log level, filters and destinations of a `Prog`
+pub struct Logger {
+    state: Mutex<State>,
+}
+
+impl Logger {
+    /// Creates a logger showing warnings and errors
+    pub const fn new() -> Logger {
+        Logger {
+            state: Mutex::new(State { level: Level::Warn, modules: Vec::new(),
+                                      timestamps: false, file: None }),
+        }
+    }
+
+    /// Returns the level for modules without their own
+    pub fn level(&self) -> Level {
+        lock(&self.state).level
+    }
+
+    /// Sets the level for modules without their own
+    pub fn set_level(&self, level: Level) {
+        lock(&self.state).level = level;
+    }
+
+    /// Sets the level for `module` and the modules inside it
+    pub fn set_module_level(&self, module: &str, level: Level) {
+        let mut state = lock(&self.state);
+        state.modules.retain(|m| m.0 != module);
+        state.modules.push((module.to_string(), level));
+    }
+
+    /// Adds the time to messages written to standard error, messages in the
+    /// log file always have it
+    pub fn set_timestamps(&self, on: bool) {
+        lock(&self.state).timestamps = on;
+    }
+
+    /// Applies a filter such as `info,util::net=trace`, a bare level sets the
+    /// default level
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::log::{Level,Logger};
+    ///
+    /// let logger = Logger::new();
+    /// logger.parse_filter("debug,util::net=trace,util::db=off").unwrap();
+    /// assert!(logger.enabled(Level::Trace, "util::net::http"));
+    /// assert!(!logger.enabled(Level::Error, "util::db"));
+    /// assert!(logger.enabled(Level::Debug, "util"));
+    /// assert!(!logger.enabled(Level::Trace, "util::network"));
+    /// ```
+    pub fn parse_filter(&self, filter: &str) -> Result<(), String> {
+        for part in filter.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            match part.find('=') {
+                Some(i) => { self.set_module_level(part[..i].trim(), part[i + 1..].parse()?); },
+                None => { self.set_level(part.parse()?); },
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if a message at `level` from `module` is shown
+    pub fn enabled(&self, level: Level, module: &str) -> bool {
+        level != Level::Off && level <= self.module_level(&lock(&self.state), module)
+    }
+
+    // The level of the longest module filter matching `module`
+    fn module_level(&self, state: &State, module: &str) -> Level {
+        state.modules.iter()
+            .filter(|m| module == m.0 || module.starts_with(&format!("{}::", m.0)))
+            .max_by_key(|m| m.0.len())
+            .map(|m| m.1)
+            .unwrap_or(state.level)
+    }
+
+    /// Also writes messages to the file at `path`, rotating it when it grows
+    /// past `max` bytes and keeping `keep` old files
+    pub fn to_file<P: AsRef<Path>>(&self, path: P, max: u64, keep: usize) -> io::Result<()> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        lock(&self.state).file = Some(LogFile { path, file, size, max, keep });
+        Ok(())
+    }
+}
+
+impl Default for Logger {
+    fn default() -> Logger {
+        Logger::new()
+    }
+}
+
+/// Logs a message at a level from `rpf::log::Level` through a `Prog`
+///
+/// # Example
+/// ```
+/// #[macro_use] extern crate rpf;
+/// use rpf::Prog;
+///
+/// fn main() {
+///     let prog = Prog::new("util", "0.1.0", "2015");
+///     rpf_log!(prog, Warn, "{} is deprecated", "--old");
+/// }
+/// ```
+#[macro_export]
+macro_rules! rpf_log {
+    ($prog:expr, $level:ident, $($arg:tt)+) => {
+        $prog.log($crate::log::Level::$level, module_path!(), format_args!($($arg)+))
+    };
+}
+
+impl Prog {
+    /// Sets the log level from `VERBOSE` and `QUIET`, clearing module levels,
+    /// then applies the `<NAME>_LOG` environment variable, exiting with
+    /// `ExitStatus::OptError` if it is invalid
+    pub fn init_log(&self, m: &Matches) {
+        let shift = m.count(VERBOSE.long) as isize - m.count(QUIET.long) as isize;
+        self.logger.set_level(Level::Warn.shift(shift));
+        lock(&self.logger.state).modules.clear();
+        let var = format!("{}_LOG", env_prefix(self.name));
+        if let Ok(filter) = env::var(&var) {
+            if let Err(e) = self.logger.parse_filter(&filter) {
+                self.error(format!("Invalid environment variable {}: {}", var, e),
+                           ExitStatus::OptError);
+            }
+        }
+    }
+
+    /// Also writes messages to `<name>.log` in the program's state directory,
+    /// returning its path
+    pub fn log_to_state_dir(&self) -> io::Result<PathBuf> {
+        let path = self.create_dir(Dir::State)?.join(format!("{}.log", self.name));
+        self.logger.to_file(&path, MAX_FILE_SIZE, KEEP_FILES)?;
+        Ok(path)
+    }
+
+    /// Logs a message, usually called through `rpf_log!`
+    pub fn log(&self, level: Level, module: &str, args: fmt::Arguments) {
+        let mut state = lock(&self.logger.state);
+        if level == Level::Off || level > self.logger.module_level(&state, module) { return; }
+        let mesg = args.to_string();
+        let time = timestamp();
+        let place = if level >= Level::Debug { format!("{}: ", module) } else { String::new() };
+        if let Some(ref mut file) = state.file {
+            file.write_line(&format!("{} {}: {}: {}{}\n", time, self.name, level, place, mesg));
+        }
+        self.bars.clear(&self.io);
+        if self.format.get().is_json() {
+            self.io.eprint(format_args!("{{\"level\":\"{}\",\"module\":{},\"message\":{}}}\n",
+                                        level, json_string(module), json_string(&mesg)));
+            return;
+        }
+        let stamp = if state.timestamps { format!("{} ", time) } else { String::new() };
+        self.io.eprint(format_args!("{}{}: {} {}{}\n", stamp, self.name,
+                                    format!("{}:", level).paint(level.color()), place, mesg));
+    }
+}
+
+// The current time as `YYYY-MM-DDTHH:MM:SSZ`
+fn timestamp() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let day = secs % 86400;
+    format!("{}T{:02}:{:02}:{:02}Z", build::date(secs), day / 3600, day / 60 % 60, day % 60)
+}
+
+// The logger remains usable after a panic while logging
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    match state.lock() {
+        Ok(guard) => { guard },
+        Err(poisoned) => { poisoned.into_inner() },
+    }
+}
+
+#[test]
+fn test_log_verbosity() {
+    use utils::{Cmd,Output};
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("rpf-log-test", "0.1.0", "2015") };
+    let cmd = Cmd::new("rpf-log-test").opt(VERBOSE).opt(QUIET);
+    prog.init_log(&cmd.parse(vec!["-vv"]).unwrap());
+    assert_eq!(prog.logger.level(), Level::Debug);
+    prog.init_log(&cmd.parse(vec!["-qqq"]).unwrap());
+    assert_eq!(prog.logger.level(), Level::Off);
+    rpf_log!(prog, Error, "hidden");
+    assert_eq!(prog.io.take_err(), "");
+    env::set_var("RPF_LOG_TEST_LOG", "trace,rpf::net=off");
+    prog.init_log(&cmd.parse(vec!["-q"]).unwrap());
+    assert_eq!(prog.logger.level(), Level::Trace);
+    assert!(!prog.logger.enabled(Level::Error, "rpf::net"));
+    env::remove_var("RPF_LOG_TEST_LOG");
+    prog.init_log(&cmd.parse(vec!["-v"]).unwrap());
+    assert!(prog.logger.enabled(Level::Info, "rpf::net"));
+}
+
+#[test]
+fn test_log_file_rotation() {
+    use utils::{Output,test};
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    fs::create_dir_all("test-log").unwrap();
+    prog.logger.to_file("test-log/util.log", 60, 2).unwrap();
+    for i in 0..4 {
+        rpf_log!(prog, Warn, "message {}", i);
+    }
+    let current = fs::read_to_string("test-log/util.log").unwrap();
+    assert!(current.ends_with("Z util: warning: message 3\n"));
+    assert!(Path::new("test-log/util.log.2").is_file());
+    assert!(!Path::new("test-log/util.log.3").exists());
+    test::remove("test-log");
+}
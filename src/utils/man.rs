@@ -0,0 +1,225 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Man page generation from a `Prog` and its `Cmd`
+
+use utils::{Cmd,ExitStatus,Opt,Prog};
+use utils::config::env_prefix;
+
+use std::fs;
+use std::io::{self,Write};
+use std::path::{Path,PathBuf};
+
+/// Hidden option that makes `Prog::parse_args` print the man page and exit
+pub static FLAG: Opt = Opt::flag("generate-man", "Print a man page and exit").hidden();
+
+/// Renders a roff man page for `cmd` in section 1
+///
+/// # Example
+/// ```
+/// use rpf::{Prog,Cmd,Opt,man};
+///
+/// let prog = Prog { description: "Does useful things", authors: "A:B",
+///                   ..Prog::new("util", "0.1.0", "2015") };
+/// let cmd = Cmd::new("util").arg("[FILE]...")
+///     .opt(Opt::flag("verbose", "Print more information").short('v'));
+/// let page = man::render(&prog, &cmd);
+/// assert!(page.starts_with(".TH UTIL 1"));
+/// assert!(page.contains(".SH OPTIONS\n.TP\n\\fB\\-v\\fR, \\fB\\-\\-verbose\\fR\n"));
+/// ```
+pub fn render(prog: &Prog, cmd: &Cmd) -> String {
+    let mut page = format!(".TH {} 1 {} {} \"User Commands\"\n",
+                           escape(&cmd.name.to_uppercase()), quote(prog.build.date),
+                           quote(&format!("{} {}", prog.name, prog.vers)));
+    page.push_str(".SH NAME\n");
+    if prog.description.is_empty() {
+        page.push_str(&format!("{}\n", escape(cmd.name)));
+    } else {
+        page.push_str(&format!("{} \\- {}\n", escape(cmd.name), escape(prog.description)));
+    }
+    page.push_str(&format!(".SH SYNOPSIS\n{}", synopsis(cmd, cmd.name)));
+    for sub in cmd.subs.iter() {
+        page.push_str(&format!(".br\n{}", synopsis(sub, &format!("{} {}", cmd.name, sub.name))));
+    }
+    if !cmd.about.is_empty() {
+        page.push_str(&format!(".SH DESCRIPTION\n{}\n", paragraphs(cmd.about)));
+    }
+    let prefix = env_prefix(prog.name);
+    if cmd.opts.iter().any(|o| !o.hidden) {
+        page.push_str(".SH OPTIONS\n");
+        page.push_str(&options(&cmd.opts, &prefix));
+    }
+    if !cmd.subs.is_empty() {
+        page.push_str(".SH COMMANDS\n");
+        for sub in cmd.subs.iter() {
+            page.push_str(&format!(".TP\n\\fB{}\\fR\n{}\n", escape(sub.name),
+                                   paragraphs(sub.about)));
+            if sub.opts.iter().any(|o| !o.hidden) {
+                page.push_str(&format!(".RS\n{}.RE\n", options(&sub.opts, &prefix)));
+            }
+        }
+    }
+    page.push_str(".SH \"EXIT STATUS\"\n");
+    for status in ExitStatus::ALL.iter() {
+        page.push_str(&format!(".TP\n{}\n{}\n", status.code(), escape(status.description())));
+    }
+    page.push_str(".TP\n128+\\fIN\\fR\nTerminated by signal \\fIN\\fR\n");
+    let mut vars: Vec<(String, String)> = cmd.env.iter()
+        .map(|&(var, help)| (var.to_string(), paragraphs(help))).collect();
+    if let Some(var) = cmd.args_env {
+        vars.push((var.to_string(), "Arguments parsed before those on the command line.".into()));
+    }
+    let mut stack = vec![cmd];
+    while let Some(c) = stack.pop() {
+        for opt in c.opts.iter().filter(|o| !o.hidden) {
+            if let Some(var) = opt.env_var(&prefix) {
+                if vars.iter().all(|v| v.0 != var) {
+                    vars.push((var, format!("Default for \\fB\\-\\-{}\\fR.\n{}",
+                                            escape(opt.long), paragraphs(opt.help))));
+                }
+            }
+        }
+        stack.extend(c.subs.iter());
+    }
+    if !vars.is_empty() {
+        page.push_str(".SH ENVIRONMENT\n");
+        for (var, help) in vars.iter() {
+            page.push_str(&format!(".TP\n\\fB{}\\fR\n{}\n", escape(var), help));
+        }
+    }
+    if !cmd.files.is_empty() {
+        page.push_str(".SH FILES\n");
+        for &(path, help) in cmd.files.iter() {
+            page.push_str(&format!(".TP\n\\fI{}\\fR\n{}\n", escape(path), paragraphs(help)));
+        }
+    }
+    let authors = prog.authors();
+    if !authors.is_empty() {
+        page.push_str(".SH AUTHORS\n");
+        let lines: Vec<String> = authors.iter().map(|a| escape(a)).collect();
+        page.push_str(&format!("{}\n", lines.join("\n.br\n")));
+    }
+    if !prog.bugs.is_empty() {
+        page.push_str(&format!(".SH \"REPORTING BUGS\"\nReport bugs to: {}\n",
+                               escape(prog.bugs)));
+    }
+    let notice = escape(prog.copyright_notice().trim_end());
+    page.push_str(&format!(".SH COPYRIGHT\n{}\n", notice.replace('\n', "\n.br\n")));
+    page
+}
+
+/// Writes the man page for `cmd` to `<dir>/<name>.1`, for use from a build
+/// script
+pub fn write<P: AsRef<Path>>(prog: &Prog, cmd: &Cmd, dir: P) -> io::Result<PathBuf> {
+    fs::create_dir_all(&dir)?;
+    let path = dir.as_ref().join(format!("{}.1", cmd.name));
+    fs::File::create(&path)?.write_all(render(prog, cmd).as_bytes())?;
+    Ok(path)
+}
+
+fn synopsis(cmd: &Cmd, name: &str) -> String {
+    let mut syn = format!(".B {}\n", escape(name));
+    if cmd.opts.iter().any(|o| !o.hidden) { syn.push_str("[\\fIOPTIONS\\fR]\n"); }
+    if !cmd.subs.is_empty() { syn.push_str("\\fICOMMAND\\fR\n"); }
+    for arg in cmd.args.iter() {
+        syn.push_str(&format!("{}\n", escape(arg)));
+    }
+    syn
+}
+
+fn options(opts: &[Opt], prefix: &str) -> String {
+    let mut text = String::new();
+    for opt in opts.iter().filter(|o| !o.hidden) {
+        text.push_str(".TP\n");
+        if let Some(c) = opt.short {
+            text.push_str(&format!("\\fB\\-{}\\fR, ", escape(&c.to_string())));
+        }
+        text.push_str(&format!("\\fB\\-\\-{}\\fR", escape(opt.long)));
+        if let Some(v) = opt.value {
+            text.push_str(&format!(" \\fI{}\\fR", escape(v)));
+        }
+        text.push_str(&format!("\n{}\n", paragraphs(opt.help)));
+        if let Some(var) = opt.env_var(prefix) {
+            text.push_str(&format!("May also be set with \\fB{}\\fR.\n", escape(&var)));
+        }
+    }
+    text
+}
+
+// Escapes text and separates paragraphs with `.PP`
+fn paragraphs(text: &str) -> String {
+    let paras: Vec<String> = text.split("\n\n").map(escape).collect();
+    paras.join("\n.PP\n")
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", escape(text).replace('"', "\\(dq"))
+}
+
+/// Escapes text for roff, including lines that would otherwise be read as
+/// requests
+///
+/// # Example
+/// ```
+/// use rpf::man;
+///
+/// assert_eq!(man::escape("a-b\\c\n.d"), "a\\-b\\ec\n\\&.d");
+/// ```
+pub fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    let lines: Vec<String> = text.lines().map(|l| {
+        if l.starts_with('.') || l.starts_with('\'') { format!("\\&{}", l) }
+        else { l.to_string() }
+    }).collect();
+    lines.join("\n")
+}
+
+impl Prog {
+    /// Prints the man page for `cmd`
+    pub fn man(&self, cmd: &Cmd) {
+        self.io.print(format_args!("{}", render(self, cmd)));
+    }
+}
+
+#[test]
+fn test_man_sections() {
+    let prog = Prog { authors: "A <a@example.com>", bugs: "bugs@example.com", license: "MIT",
+                      ..Prog::new("util", "0.1.0", "2015") };
+    let cmd = Cmd::new("util").about("First.\n\nSecond.")
+        .opt(Opt::value("jobs", "N", "Run N jobs").short('j'))
+        .opt(FLAG)
+        .subcommand(Cmd::new("add").about("Add things"))
+        .env("UTIL_COLOR", "When to use colour")
+        .file("~/.config/util/config", "User configuration");
+    let page = render(&prog, &cmd);
+    for section in ["NAME", "SYNOPSIS", "DESCRIPTION", "OPTIONS", "COMMANDS",
+                    "\"EXIT STATUS\"", "ENVIRONMENT", "FILES", "AUTHORS",
+                    "\"REPORTING BUGS\"", "COPYRIGHT"].iter() {
+        assert!(page.contains(&format!(".SH {}\n", section)), "missing {}", section);
+    }
+    assert!(page.contains("First.\n.PP\nSecond.\n"));
+    assert!(page.contains(".TP\n2\nInvalid options\n"));
+    assert!(!page.contains("generate"));
+}
+
+#[test]
+fn test_man_env() {
+    let prog = Prog::new("util", "0.1.0", "2015");
+    let cmd = Cmd::new("util").opt(Opt::value("color", "WHEN", "Colorize").env_auto());
+    let page = render(&prog, &cmd);
+    assert!(page.contains("Colorize\nMay also be set with \\fBUTIL_COLOR\\fR.\n"));
+    assert!(page.contains(".SH ENVIRONMENT\n.TP\n\\fBUTIL_COLOR\\fR\n\
+                           Default for \\fB\\-\\-color\\fR.\nColorize\n"));
+}
+
+#[test]
+fn test_man_write() {
+    use utils::test;
+
+    let prog = Prog::new("util", "0.1.0", "2015");
+    let path = write(&prog, &Cmd::new("util"), "test-man").unwrap();
+    assert_eq!(path, PathBuf::from("test-man/util.1"));
+    assert!(path.is_file());
+    test::remove("test-man");
+}
@@ -3,14 +3,43 @@
 // BSD 3-Clause license. For full terms please see the LICENSE file.
 
 pub use utils::prog::{Prog};
+pub use utils::opt::{Cmd,Env,Hint,Matches,Opt,ParseError,HELP,VERSION};
+pub use utils::output::{Output,Stream};
 pub use utils::as_path::*;
-pub use utils::status::{Exit,ExitStatus};
+pub use utils::config::{Config,ConfigError,env_prefix};
+pub use utils::dirs::{Dir,base_dir,home_dir};
+pub use utils::status::{Exit,ExitStatus,ExitStrategy,OnExit,catch_exit};
 pub use utils::styled::*;
 pub use utils::pathmod::{PathMod};
+pub use utils::progress::{Bar,Bars};
+pub use utils::prompt::{Prompt,PromptError};
 
+pub mod args;
 pub mod as_path;
+pub mod build;
+pub mod complete;
+pub mod config;
+#[cfg(unix)]
+pub mod daemon;
+pub mod dirs;
+pub mod format;
+pub mod license;
+pub mod log;
+pub mod man;
+pub mod opt;
+pub mod output;
+pub mod pager;
 pub mod prog;
+pub mod progress;
+pub mod prompt;
 pub mod pathmod;
+pub mod pipeline;
+pub mod process;
+pub mod signal;
 pub mod status;
 pub mod styled;
+pub mod table;
+pub mod term;
 pub mod test;
+pub mod text;
+pub mod value;
@@ -0,0 +1,920 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+use utils::{Exit,ExitStatus,Prog};
+use utils::config::env_prefix;
+use utils::args;
+use utils::man;
+use utils::term;
+use utils::text;
+use utils::complete::{self,Shell};
+
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+
+/// What kind of value an option or argument takes, used when completing it
+#[derive(Copy,Clone)]
+pub enum Hint {
+    /// Any value
+    Any,
+    /// A file path
+    File,
+    /// A directory path
+    Dir,
+    /// One of a fixed list of values
+    Choices(&'static [&'static str]),
+    /// Values returned by a function given the words before the one being
+    /// completed and the text typed so far, only used by dynamic completion
+    Dynamic(fn(&[&str], &str) -> Vec<String>),
+}
+
+impl fmt::Debug for Hint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Hint::Any => { write!(f, "Any") },
+            Hint::File => { write!(f, "File") },
+            Hint::Dir => { write!(f, "Dir") },
+            Hint::Choices(c) => { write!(f, "Choices({:?})", c) },
+            Hint::Dynamic(_) => { write!(f, "Dynamic") },
+        }
+    }
+}
+
+/// Environment variable backing an option when it is not given on the command
+/// line
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum Env {
+    /// The option is only read from the command line
+    Off,
+    /// The variable name is derived from the program name and the option's long
+    /// name, `UTIL_LOG_LEVEL` for `--log-level` of `util`
+    Auto,
+    /// The named variable
+    Var(&'static str),
+}
+
+/// A command-line option
+///
+/// # Example
+///
+/// ```
+/// use rpf::Opt;
+///
+/// static VERBOSE: Opt = Opt::flag("verbose", "Print more information").short('v');
+/// static OUTPUT: Opt = Opt::value("output", "FILE", "Write output to FILE").short('o');
+/// assert_eq!(VERBOSE.short, Some('v'));
+/// assert_eq!(OUTPUT.value, Some("FILE"));
+/// ```
+#[derive(Copy,Clone,Debug)]
+pub struct Opt {
+    /// Long name, used as `--long`
+    pub long: &'static str,
+    /// Short name, used as `-s`
+    pub short: Option<char>,
+    /// Name of the option's value, `None` for flags
+    pub value: Option<&'static str>,
+    /// Description of the option
+    pub help: &'static str,
+    /// Hidden options are accepted but left out of help and documentation
+    pub hidden: bool,
+    /// Kind of value the option takes
+    pub hint: Hint,
+    /// Environment variable backing the option
+    pub env: Env,
+}
+
+impl Opt {
+    /// Creates an option that takes no value
+    pub const fn flag(long: &'static str, help: &'static str) -> Opt {
+        Opt { long, short: None, value: None, help, hidden: false, hint: Hint::Any,
+              env: Env::Off }
+    }
+
+    /// Creates an option that takes a value named `value`
+    pub const fn value(long: &'static str, value: &'static str, help: &'static str) -> Opt {
+        Opt { long, short: None, value: Some(value), help, hidden: false, hint: Hint::Any,
+              env: Env::Off }
+    }
+
+    /// Sets the option's short name
+    pub const fn short(mut self, short: char) -> Opt {
+        self.short = Some(short);
+        self
+    }
+
+    /// Hides the option from help and documentation
+    pub const fn hidden(mut self) -> Opt {
+        self.hidden = true;
+        self
+    }
+
+    /// Sets the kind of value the option takes
+    pub const fn hint(mut self, hint: Hint) -> Opt {
+        self.hint = hint;
+        self
+    }
+
+    /// Restricts completion of the option's value to `choices`
+    pub const fn choices(self, choices: &'static [&'static str]) -> Opt {
+        self.hint(Hint::Choices(choices))
+    }
+
+    /// Reads the option from the environment variable `var` when it is not
+    /// given on the command line. Flags are set by `1`, `true`, `yes` or `on`
+    /// and left unset by `0`, `false`, `no`, `off` or an empty value.
+    pub const fn env(mut self, var: &'static str) -> Opt {
+        self.env = Env::Var(var);
+        self
+    }
+
+    /// Like `env` with a variable named after the program, see `Env::Auto`
+    pub const fn env_auto(mut self) -> Opt {
+        self.env = Env::Auto;
+        self
+    }
+
+    /// Returns the environment variable backing the option, `prefix` is used
+    /// for `Env::Auto` and is usually `env_prefix(prog.name)`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rpf::Opt;
+    ///
+    /// let opt = Opt::value("log-level", "LEVEL", "Set the log level").env_auto();
+    /// assert_eq!(opt.env_var("UTIL"), Some("UTIL_LOG_LEVEL".to_string()));
+    /// ```
+    pub fn env_var(&self, prefix: &str) -> Option<String> {
+        match self.env {
+            Env::Off => { None },
+            Env::Auto => { Some(format!("{}_{}", prefix, env_prefix(self.long))) },
+            Env::Var(var) => { Some(var.to_string()) },
+        }
+    }
+
+    /// Returns how the option is written in help, such as `-o, --output FILE`
+    pub fn signature(&self) -> String {
+        let mut sig = match self.short {
+            Some(c) => { format!("-{}, --{}", c, self.long) },
+            None => { format!("    --{}", self.long) },
+        };
+        if let Some(v) = self.value {
+            sig.push_str(&format!(" {}", v));
+        }
+        sig
+    }
+}
+
+/// A program or subcommand definition, used both to parse arguments and to
+/// generate documentation
+///
+/// # Example
+///
+/// ```
+/// use rpf::{Cmd,Opt};
+///
+/// let cmd = Cmd::new("util")
+///     .about("Does useful things")
+///     .arg("[FILE]...")
+///     .opt(Opt::flag("verbose", "Print more information").short('v'))
+///     .opt(Opt::value("jobs", "N", "Run N jobs at once").short('j'));
+/// let m = cmd.parse(vec!["-vj", "4", "a.txt"]).unwrap();
+/// assert_eq!(m.count("verbose"), 1);
+/// assert_eq!(m.value("jobs"), Some("4"));
+/// assert_eq!(m.free(), &["a.txt".to_string()]);
+/// ```
+#[derive(Clone,Debug)]
+pub struct Cmd {
+    /// Name of the command
+    pub name: &'static str,
+    /// Description of the command
+    pub about: &'static str,
+    /// Positional arguments as written in the synopsis, such as `[FILE]...`
+    pub args: Vec<&'static str>,
+    /// Kind of value positional arguments take
+    pub args_hint: Hint,
+    /// Options accepted by the command
+    pub opts: Vec<Opt>,
+    /// Subcommands of the command
+    pub subs: Vec<Cmd>,
+    /// Environment variables the command reads and their descriptions
+    pub env: Vec<(&'static str, &'static str)>,
+    /// Files the command uses and their descriptions
+    pub files: Vec<(&'static str, &'static str)>,
+    /// Whether `@file` arguments are replaced with the arguments in `file`
+    pub response_files: bool,
+    /// Environment variable holding arguments parsed before the command line
+    pub args_env: Option<&'static str>,
+}
+
+impl Cmd {
+    /// Creates a command named `name` with no options
+    pub fn new(name: &'static str) -> Cmd {
+        Cmd {
+            name,
+            about: "",
+            args: Vec::new(),
+            args_hint: Hint::Any,
+            opts: Vec::new(),
+            subs: Vec::new(),
+            env: Vec::new(),
+            files: Vec::new(),
+            response_files: false,
+            args_env: None,
+        }
+    }
+
+    /// Sets the description
+    pub fn about(mut self, about: &'static str) -> Cmd {
+        self.about = about;
+        self
+    }
+
+    /// Adds a positional argument to the synopsis
+    pub fn arg(mut self, arg: &'static str) -> Cmd {
+        self.args.push(arg);
+        self
+    }
+
+    /// Sets the kind of value positional arguments take
+    pub fn args_hint(mut self, hint: Hint) -> Cmd {
+        self.args_hint = hint;
+        self
+    }
+
+    /// Adds an option
+    pub fn opt(mut self, opt: Opt) -> Cmd {
+        self.opts.push(opt);
+        self
+    }
+
+    /// Adds a subcommand
+    pub fn subcommand(mut self, sub: Cmd) -> Cmd {
+        self.subs.push(sub);
+        self
+    }
+
+    /// Documents an environment variable the command reads
+    pub fn env(mut self, var: &'static str, help: &'static str) -> Cmd {
+        self.env.push((var, help));
+        self
+    }
+
+    /// Documents a file the command uses
+    pub fn file(mut self, path: &'static str, help: &'static str) -> Cmd {
+        self.files.push((path, help));
+        self
+    }
+
+    /// Replaces `@file` arguments with the arguments read from `file`, see
+    /// `args::expand`
+    pub fn response_files(mut self) -> Cmd {
+        self.response_files = true;
+        self
+    }
+
+    /// Parses the arguments in the environment variable `var` before the
+    /// command line, so options given on the command line take precedence
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rpf::{Cmd,Opt};
+    ///
+    /// std::env::set_var("UTIL_OPTS", "--jobs 4 -v");
+    /// let cmd = Cmd::new("util").args_env("UTIL_OPTS")
+    ///     .opt(Opt::value("jobs", "N", "Run N jobs"))
+    ///     .opt(Opt::flag("verbose", "Print more").short('v'));
+    /// let m = cmd.parse(vec!["--jobs", "8"]).unwrap();
+    /// assert_eq!(m.value("jobs"), Some("8"));
+    /// assert!(m.has("verbose"));
+    /// ```
+    pub fn args_env(mut self, var: &'static str) -> Cmd {
+        self.args_env = Some(var);
+        self
+    }
+
+    /// Returns the option with the long name `long`
+    pub fn find(&self, long: &str) -> Option<&Opt> {
+        self.opts.iter().find(|o| o.long == long)
+    }
+
+    /// Returns the synopsis, such as `util [OPTIONS] [FILE]...`
+    pub fn synopsis(&self) -> String {
+        let mut syn = self.name.to_string();
+        if self.opts.iter().any(|o| !o.hidden) { syn.push_str(" [OPTIONS]"); }
+        if !self.subs.is_empty() { syn.push_str(" COMMAND"); }
+        for arg in self.args.iter() {
+            syn.push(' ');
+            syn.push_str(arg);
+        }
+        syn
+    }
+
+    /// Parses arguments, not including the program name. Options backed by
+    /// `Env::Auto` use the command's name as the environment variable prefix.
+    pub fn parse<I, T>(&self, args: I) -> Result<Matches, ParseError>
+        where I: IntoIterator<Item=T>, T: Into<String> {
+        self.parse_env(args, &env_prefix(self.name))
+    }
+
+    /// Parses arguments like `parse`, using `prefix` for `Env::Auto` options
+    pub fn parse_env<I, T>(&self, args: I, prefix: &str) -> Result<Matches, ParseError>
+        where I: IntoIterator<Item=T>, T: Into<String> {
+        let mut args: Vec<String> = args.into_iter().map(|a| a.into()).collect();
+        if let Some(var) = self.args_env {
+            if let Ok(line) = env::var(var) {
+                let mut extra = args::split(&line).map_err(|e| {
+                    ParseError::new(self.name, format!("{} in environment variable {}", e, var))
+                })?;
+                extra.append(&mut args);
+                args = extra;
+            }
+        }
+        if self.response_files {
+            args = args::expand(args).map_err(|e| ParseError::new(self.name, e))?;
+        }
+        let mut m = self.parse_in(&args, self.name)?;
+        // Help and version are printed whatever the environment holds
+        if !m.wants(HELP.long) && !m.wants(VERSION.long) {
+            self.read_env(&mut m, prefix)?;
+        }
+        Ok(m)
+    }
+
+    fn parse_in(&self, args: &[String], path: &str) -> Result<Matches, ParseError> {
+        let mut m = Matches { path: path.to_string(), ..Matches::default() };
+        let mut i = 0;
+        while i < args.len() {
+            let arg = &args[i];
+            i += 1;
+            if arg == "--" {
+                m.free.extend(args[i..].iter().cloned());
+                break;
+            }
+            if let Some(long) = arg.strip_prefix("--") {
+                let (name, inline) = match long.find('=') {
+                    Some(n) => { (&long[..n], Some(long[n + 1..].to_string())) },
+                    None => { (long, None) },
+                };
+                let opt = match self.find(name) {
+                    Some(o) => { o },
+                    None => {
+                        return Err(ParseError::new(path, format!("Unknown option '--{}'", name)));
+                    },
+                };
+                match (opt.value, inline) {
+                    (Some(_), Some(v)) => { m.push(opt.long, Some(v)); },
+                    (Some(_), None) => {
+                        if i >= args.len() {
+                            return Err(ParseError::new(path, format!(
+                                "Option '--{}' requires a value", name)));
+                        }
+                        m.push(opt.long, Some(args[i].clone()));
+                        i += 1;
+                    },
+                    (None, Some(_)) => {
+                        return Err(ParseError::new(path, format!(
+                            "Option '--{}' does not take a value", name)));
+                    },
+                    (None, None) => { m.push(opt.long, None); },
+                }
+            } else if arg.len() > 1 && arg.starts_with('-') {
+                let shorts: Vec<char> = arg[1..].chars().collect();
+                let mut k = 0;
+                while k < shorts.len() {
+                    let c = shorts[k];
+                    k += 1;
+                    let opt = match self.opts.iter().find(|o| o.short == Some(c)) {
+                        Some(o) => { o },
+                        None => {
+                            return Err(ParseError::new(path, format!("Unknown option '-{}'", c)));
+                        },
+                    };
+                    if opt.value.is_none() {
+                        m.push(opt.long, None);
+                        continue;
+                    }
+                    // The rest of the argument or the next argument is the value
+                    if k < shorts.len() {
+                        m.push(opt.long, Some(shorts[k..].iter().collect()));
+                    } else if i < args.len() {
+                        m.push(opt.long, Some(args[i].clone()));
+                        i += 1;
+                    } else {
+                        return Err(ParseError::new(path, format!(
+                            "Option '-{}' requires a value", c)));
+                    }
+                    break;
+                }
+            } else if m.free.is_empty() && !self.subs.is_empty() {
+                let sub = match self.subs.iter().find(|s| s.name == arg) {
+                    Some(s) => { s },
+                    None => {
+                        return Err(ParseError::new(path, format!("Unknown command '{}'", arg)));
+                    },
+                };
+                let sub_path = format!("{} {}", path, sub.name);
+                let sub_m = sub.parse_in(&args[i..], &sub_path)?;
+                m.sub = Some((sub.name.to_string(), Box::new(sub_m)));
+                break;
+            } else {
+                m.free.push(arg.clone());
+            }
+        }
+        Ok(m)
+    }
+
+    // Fills in options missing from the command line from their environment
+    // variables, for subcommands first
+    fn read_env(&self, m: &mut Matches, prefix: &str) -> Result<(), ParseError> {
+        if let Some((ref name, ref mut sub_m)) = m.sub {
+            if let Some(sub) = self.subs.iter().find(|s| s.name == name) {
+                sub.read_env(sub_m, prefix)?;
+            }
+        }
+        for opt in self.opts.iter() {
+            let var = match opt.env_var(prefix) {
+                Some(v) => { v },
+                None => { continue; },
+            };
+            if m.has(opt.long) { continue; }
+            let val = match env::var(&var) {
+                Ok(v) => { v },
+                Err(_) => { continue; },
+            };
+            if opt.value.is_some() {
+                m.push(opt.long, Some(val));
+            } else {
+                match &val.to_lowercase()[..] {
+                    "1" | "true" | "yes" | "on" => { m.push(opt.long, None); },
+                    "" | "0" | "false" | "no" | "off" => { continue; },
+                    _ => {
+                        return Err(ParseError::new(&m.path, format!(
+                            "Invalid value '{}' for '--{}' from environment variable {}",
+                            val, opt.long, var)));
+                    },
+                }
+            }
+            m.env.push((opt.long, var));
+        }
+        Ok(())
+    }
+
+    /// Returns help text listing the command's usage, options and
+    /// subcommands, `prefix` is used for `Env::Auto` options. Descriptions are
+    /// wrapped to `cols` columns.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rpf::{Cmd,Opt};
+    ///
+    /// let cmd = Cmd::new("util")
+    ///     .opt(Opt::flag("verbose", "Print more information").short('v'))
+    ///     .opt(Opt::value("color", "WHEN", "Colorize output").env_auto());
+    /// let help = cmd.help("UTIL", 80);
+    /// assert!(help.starts_with("Usage: util [OPTIONS]\n\nOptions:\n"));
+    /// assert!(help.contains("\n  -v, --verbose       Print more information\n"));
+    /// assert!(help.contains("\n      --color WHEN    Colorize output [env: UTIL_COLOR]\n"));
+    /// ```
+    pub fn help(&self, prefix: &str, cols: usize) -> String {
+        let mut out = format!("Usage: {}\n", self.synopsis());
+        if !self.about.is_empty() {
+            out.push('\n');
+            out.push_str(&text::fill(self.about, cols, "", ""));
+        }
+        let opts: Vec<&Opt> = self.opts.iter().filter(|o| !o.hidden).collect();
+        let width = opts.iter().map(|o| o.signature().len())
+            .chain(self.subs.iter().map(|s| s.name.len())).max().unwrap_or(0) + 4;
+        if !opts.is_empty() {
+            out.push_str("\nOptions:\n");
+            for opt in opts.iter() {
+                let mut help = opt.help.to_string();
+                if let Some(var) = opt.env_var(prefix) {
+                    help.push_str(&format!(" [env: {}]", var));
+                }
+                let first = format!("  {:w$}", opt.signature(), w = width);
+                out.push_str(&text::fill(&help, cols, &first, &" ".repeat(width + 2)));
+            }
+        }
+        if !self.subs.is_empty() {
+            out.push_str("\nCommands:\n");
+            for sub in self.subs.iter() {
+                let first = format!("  {:w$}", sub.name, w = width);
+                out.push_str(&text::fill(sub.about, cols, &first, &" ".repeat(width + 2)));
+            }
+        }
+        out
+    }
+}
+
+/// Options and arguments found while parsing
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct Matches {
+    path: String,
+    opts: Vec<(&'static str, Option<String>)>,
+    env: Vec<(&'static str, String)>,
+    free: Vec<String>,
+    sub: Option<(String, Box<Matches>)>,
+}
+
+impl Matches {
+    /// Returns the environment variable an option was read from, `None` if it
+    /// was given on the command line or not at all
+    pub fn env_var(&self, long: &str) -> Option<&str> {
+        self.env.iter().find(|e| e.0 == long).map(|e| &e.1[..])
+    }
+
+    /// Parses the last value given for an option. The error names the
+    /// environment variable the value came from if it was not given on the
+    /// command line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rpf::{Cmd,Opt};
+    ///
+    /// std::env::set_var("UTIL_JOBS", "many");
+    /// let cmd = Cmd::new("util").opt(Opt::value("jobs", "N", "Run N jobs").env_auto());
+    /// let m = cmd.parse(Vec::<String>::new()).unwrap();
+    /// assert_eq!(m.value_of::<u32>("jobs").unwrap_err().mesg,
+    ///            "Invalid value 'many' for '--jobs' from environment variable UTIL_JOBS");
+    /// ```
+    pub fn value_of<T: FromStr>(&self, long: &str) -> Result<Option<T>, ParseError> {
+        match self.value(long) {
+            Some(v) => {
+                match v.parse::<T>() {
+                    Ok(t) => { Ok(Some(t)) },
+                    Err(_) => { Err(self.invalid(long, v, "")) },
+                }
+            },
+            None => { Ok(None) },
+        }
+    }
+
+    /// Converts the last value given for an option with `parser`, see the
+    /// `value` module
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rpf::{Cmd,Opt,value};
+    /// use std::time::Duration;
+    ///
+    /// let cmd = Cmd::new("util").opt(Opt::value("timeout", "TIME", "Give up after TIME"));
+    /// let m = cmd.parse(vec!["--timeout", "5m"]).unwrap();
+    /// assert_eq!(m.parse_with("timeout", value::duration).unwrap(),
+    ///            Some(Duration::from_secs(300)));
+    /// ```
+    pub fn parse_with<T, F>(&self, long: &str, parser: F) -> Result<Option<T>, ParseError>
+        where F: Fn(&str) -> Result<T, String> {
+        match self.value(long) {
+            Some(v) => { parser(v).map(Some).map_err(|e| self.invalid(long, v, &e)) },
+            None => { Ok(None) },
+        }
+    }
+
+    /// Converts every value given for an option with `parser`
+    pub fn parse_all<T, F>(&self, long: &str, parser: F) -> Result<Vec<T>, ParseError>
+        where F: Fn(&str) -> Result<T, String> {
+        self.values(long).into_iter()
+            .map(|v| parser(v).map_err(|e| self.invalid(long, v, &e)))
+            .collect()
+    }
+
+    /// Returns an error for an invalid value of an option, naming the
+    /// environment variable the value came from if any. `reason` is appended
+    /// to the message if it is not empty.
+    pub fn invalid(&self, long: &str, value: &str, reason: &str) -> ParseError {
+        let mut mesg = format!("Invalid value '{}' for '--{}'", value, long);
+        if let Some(var) = self.env_var(long) {
+            mesg.push_str(&format!(" from environment variable {}", var));
+        }
+        if !reason.is_empty() {
+            mesg.push_str(&format!(": {}", reason));
+        }
+        ParseError::new(&self.path, mesg)
+    }
+
+    fn push(&mut self, long: &'static str, value: Option<String>) {
+        self.opts.push((long, value));
+    }
+
+    /// Returns true if the option was given
+    pub fn has(&self, long: &str) -> bool {
+        self.count(long) > 0
+    }
+
+    // Returns true if the option was given to the command or a subcommand
+    fn wants(&self, long: &str) -> bool {
+        self.has(long) || self.sub.as_ref().map(|s| s.1.wants(long)) == Some(true)
+    }
+
+    /// Returns how many times the option was given
+    pub fn count(&self, long: &str) -> usize {
+        self.opts.iter().filter(|o| o.0 == long).count()
+    }
+
+    /// Returns the last value given for the option
+    pub fn value(&self, long: &str) -> Option<&str> {
+        self.opts.iter().rev().filter(|o| o.0 == long)
+            .filter_map(|o| o.1.as_ref()).map(|v| &v[..]).next()
+    }
+
+    /// Returns every value given for the option, in order
+    pub fn values(&self, long: &str) -> Vec<&str> {
+        self.opts.iter().filter(|o| o.0 == long)
+            .filter_map(|o| o.1.as_ref()).map(|v| &v[..]).collect()
+    }
+
+    /// Returns every option given and its value, in order
+    pub fn opts(&self) -> &[(&'static str, Option<String>)] {
+        &self.opts
+    }
+
+    /// Returns positional arguments
+    pub fn free(&self) -> &[String] {
+        &self.free
+    }
+
+    /// Returns the subcommand given and its matches
+    pub fn subcommand(&self) -> Option<(&str, &Matches)> {
+        self.sub.as_ref().map(|s| (&s.0[..], &*s.1))
+    }
+}
+
+/// Error for arguments that do not match a `Cmd`
+#[derive(Clone,Debug,PartialEq)]
+pub struct ParseError {
+    /// Command, including parent commands, the error occurred in
+    pub cmd: String,
+    /// Description of the error
+    pub mesg: String,
+}
+
+impl ParseError {
+    fn new(cmd: &str, mesg: String) -> ParseError {
+        ParseError { cmd: cmd.to_string(), mesg }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mesg)
+    }
+}
+
+/// Option that makes `Prog::parse_args` print help and exit
+pub static HELP: Opt = Opt::flag("help", "Print help and exit").short('h');
+
+/// Option that makes `Prog::parse_args` print the version and exit
+pub static VERSION: Opt = Opt::flag("version", "Print version information and exit")
+    .short('V');
+
+impl Prog {
+    /// Prints the program's banner, help for `cmd` wrapped to the terminal's
+    /// width and where to report bugs
+    pub fn help(&self, cmd: &Cmd) {
+        let help = cmd.help(&env_prefix(self.name), term::width());
+        self.io.print(format_args!("{}\n{}", self.banner(), help));
+        if !self.bugs.is_empty() || !self.homepage.is_empty() {
+            self.io.print(format_args!("\n"));
+            self.bug_report();
+        }
+    }
+
+    /// Parses the process's arguments using `cmd`, exiting with
+    /// `ExitStatus::OptError` on invalid arguments. If `cmd` includes `HELP`,
+    /// `VERSION`, `man::FLAG` or `complete::FLAG` and it is given, the
+    /// corresponding output is printed and the program exits. `HELP` and
+    /// `VERSION` are also honoured after a subcommand, help being shown for
+    /// the last subcommand given, in a pager, see `Prog::pager`.
+    pub fn parse_args(&self, cmd: &Cmd) -> Matches {
+        self.parse_from(cmd, env::args().skip(1))
+    }
+
+    /// Parses `args`, not including the program name, like `parse_args`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rpf::{Prog,Cmd,Opt,Output,ExitStatus,ExitStrategy};
+    ///
+    /// let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    /// prog.on_exit.set_strategy(ExitStrategy::Return);
+    /// let cmd = Cmd::new("util").opt(Opt::flag("all", "Show everything").short('a'));
+    /// prog.parse_from(&cmd, vec!["--bogus"]);
+    /// assert_eq!(prog.on_exit.status(), Some(ExitStatus::OptError));
+    /// ```
+    pub fn parse_from<I, T>(&self, cmd: &Cmd, args: I) -> Matches
+        where I: IntoIterator<Item=T>, T: Into<String> {
+        let args: Vec<String> = args.into_iter().map(|a| a.into()).collect();
+        let dynamic = cmd.find(complete::FLAG.long).is_some();
+        if dynamic && args.first().map(|a| a == complete::DYNAMIC) == Some(true) {
+            // Words to complete follow the marker and an optional `--`
+            let skip = if args.get(1).map(|a| a == "--") == Some(true) { 2 } else { 1 };
+            for word in complete::candidates(cmd, &args[skip..]).iter() {
+                self.io.print(format_args!("{}\n", word));
+            }
+            self.exit(ExitStatus::Ok);
+            return Matches::default();
+        }
+        match cmd.parse_env(args, &env_prefix(self.name)) {
+            Ok(ref m) if m.wants(HELP.long) => {
+                let _pager = self.pager(m);
+                let mut cmd = cmd;
+                let mut m = m;
+                while let Some((name, sub)) = m.subcommand() {
+                    match cmd.subs.iter().find(|s| s.name == name) {
+                        Some(c) => { cmd = c; m = sub; },
+                        None => { break; },
+                    }
+                }
+                self.help(cmd);
+                self.exit(ExitStatus::Ok);
+                Matches::default()
+            },
+            Ok(ref m) if m.wants(VERSION.long) => {
+                self.version();
+                self.exit(ExitStatus::Ok);
+                Matches::default()
+            },
+            Ok(ref m) if m.has(man::FLAG.long) => {
+                self.man(cmd);
+                self.exit(ExitStatus::Ok);
+                Matches::default()
+            },
+            Ok(ref m) if m.has(complete::FLAG.long) => {
+                match m.value(complete::FLAG.long).unwrap_or("").parse::<Shell>() {
+                    Ok(shell) => {
+                        self.io.print(format_args!("{}", complete::generate(shell, cmd)));
+                        self.exit(ExitStatus::Ok);
+                    },
+                    Err(e) => { self.error(e, ExitStatus::OptError); },
+                }
+                Matches::default()
+            },
+            Ok(m) => { m },
+            Err(e) => {
+                self.opt_error(&e);
+                Matches::default()
+            },
+        }
+    }
+
+    /// Converts the last value given for an option with `parser`, exiting
+    /// with `ExitStatus::OptError` if it is invalid
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rpf::{Cmd,Opt,Prog,value};
+    ///
+    /// let prog = Prog::new("util", "0.1.0", "2015");
+    /// let cmd = Cmd::new("util").opt(Opt::value("size", "SIZE", "Limit output to SIZE"));
+    /// let m = prog.parse_from(&cmd, vec!["--size", "2MiB"]);
+    /// assert_eq!(prog.value(&m, "size", value::bytes), Some(2097152));
+    /// ```
+    pub fn value<T, F>(&self, m: &Matches, long: &str, parser: F) -> Option<T>
+        where F: Fn(&str) -> Result<T, String> {
+        match m.parse_with(long, parser) {
+            Ok(v) => { v },
+            Err(e) => {
+                self.opt_error(&e);
+                None
+            },
+        }
+    }
+
+    /// Reports a `ParseError` and exits with `ExitStatus::OptError`
+    pub fn opt_error(&self, e: &ParseError) {
+        self.error(format!("{}\nTry '{} --help' for more information", e.mesg, e.cmd),
+                   ExitStatus::OptError);
+    }
+}
+
+#[test]
+fn test_opt_parse_long() {
+    let cmd = Cmd::new("util")
+        .opt(Opt::value("color", "WHEN", "Colorize output"))
+        .opt(Opt::flag("quiet", "Print less"));
+    let m = cmd.parse(vec!["--color=never", "a", "--quiet", "--color", "auto", "--", "--quiet"])
+        .unwrap();
+    assert_eq!(m.values("color"), vec!["never", "auto"]);
+    assert_eq!(m.value("color"), Some("auto"));
+    assert_eq!(m.count("quiet"), 1);
+    assert_eq!(m.free(), &["a".to_string(), "--quiet".to_string()]);
+}
+
+#[test]
+fn test_opt_parse_errors() {
+    let cmd = Cmd::new("util").opt(Opt::value("jobs", "N", "Jobs").short('j'))
+        .opt(Opt::flag("all", "All"));
+    assert_eq!(cmd.parse(vec!["-x"]).unwrap_err().mesg, "Unknown option '-x'");
+    assert_eq!(cmd.parse(vec!["--jobs"]).unwrap_err().mesg,
+               "Option '--jobs' requires a value");
+    assert_eq!(cmd.parse(vec!["--all=yes"]).unwrap_err().mesg,
+               "Option '--all' does not take a value");
+    assert_eq!(cmd.parse(vec!["-j4"]).unwrap().value("jobs"), Some("4"));
+}
+
+#[test]
+fn test_opt_parse_subcommand() {
+    let cmd = Cmd::new("util").opt(Opt::flag("verbose", "More").short('v'))
+        .subcommand(Cmd::new("add").opt(Opt::flag("force", "Force").short('f')));
+    let m = cmd.parse(vec!["-v", "add", "-f", "file"]).unwrap();
+    let (name, sub) = m.subcommand().unwrap();
+    assert_eq!(name, "add");
+    assert!(sub.has("force"));
+    assert_eq!(sub.free(), &["file".to_string()]);
+    let err = cmd.parse(vec!["add", "-v"]).unwrap_err();
+    assert_eq!(err.cmd, "util add");
+    assert!(cmd.parse(vec!["remove"]).is_err());
+}
+
+#[test]
+fn test_opt_env() {
+    env::set_var("RPF_OPT_TEST_COLOR", "never");
+    env::set_var("RPF_OPT_TEST_QUIET", "yes");
+    env::set_var("OPT_TEST_FORCE", "maybe");
+    let cmd = Cmd::new("rpf-opt-test")
+        .opt(Opt::value("color", "WHEN", "Colorize").env_auto())
+        .opt(Opt::flag("quiet", "Print less").env("RPF_OPT_TEST_QUIET"));
+    let m = cmd.parse(vec!["--color", "auto"]).unwrap();
+    assert_eq!(m.value("color"), Some("auto"));
+    assert_eq!(m.env_var("color"), None);
+    assert_eq!(m.env_var("quiet"), Some("RPF_OPT_TEST_QUIET"));
+    let m = cmd.parse(Vec::<String>::new()).unwrap();
+    assert_eq!(m.value("color"), Some("never"));
+    let cmd = cmd.opt(Opt::flag("force", "Force").env("OPT_TEST_FORCE"));
+    assert_eq!(cmd.parse(Vec::<String>::new()).unwrap_err().mesg,
+               "Invalid value 'maybe' for '--force' from environment variable OPT_TEST_FORCE");
+    let cmd = cmd.opt(HELP).opt(VERSION).subcommand(Cmd::new("add").opt(HELP));
+    assert!(cmd.parse(vec!["--help"]).unwrap().has("help"));
+    assert!(cmd.parse(vec!["-V"]).unwrap().has("version"));
+    assert!(cmd.parse(vec!["add", "-h"]).is_ok());
+}
+
+#[test]
+fn test_opt_parse_with() {
+    use utils::{ExitStrategy,Output,catch_exit,value};
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    prog.on_exit.set_strategy(ExitStrategy::Unwind);
+    let cmd = Cmd::new("util").opt(Opt::value("level", "N", "Level"));
+    let m = cmd.parse(vec!["--level", "3", "--level", "9"]).unwrap();
+    assert_eq!(m.parse_all("level", value::range(0, 5)).unwrap_err().mesg,
+               "Invalid value '9' for '--level': must be between 0 and 5");
+    assert_eq!(catch_exit(|| prog.value(&m, "level", value::range(0, 5))),
+               Err(ExitStatus::OptError));
+    let err = prog.io.take_err();
+    assert!(err.contains("Try 'util --help' for more information"));
+}
+
+#[test]
+fn test_opt_response_files() {
+    use std::fs;
+
+    fs::write("test-opt.rsp", "--jobs 2\n").unwrap();
+    let cmd = Cmd::new("util").opt(Opt::value("jobs", "N", "Run N jobs"));
+    let m = cmd.parse(vec!["@test-opt.rsp"]).unwrap();
+    assert_eq!(m.free(), &["@test-opt.rsp".to_string()]);
+    let cmd = cmd.response_files();
+    let m = cmd.parse(vec!["@test-opt.rsp"]).unwrap();
+    assert_eq!(m.value("jobs"), Some("2"));
+    fs::remove_file("test-opt.rsp").unwrap();
+    assert!(cmd.parse(vec!["@test-opt.rsp"]).unwrap_err().mesg.starts_with("test-opt.rsp: "));
+}
+
+#[test]
+fn test_opt_dynamic_completion() {
+    use utils::{ExitStrategy,Output};
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    prog.on_exit.set_strategy(ExitStrategy::Return);
+    let cmd = Cmd::new("util").opt(Opt::flag("verbose", "More"));
+    prog.parse_from(&cmd, vec![complete::DYNAMIC, "--", "--v"]);
+    assert_eq!(prog.on_exit.status(), Some(ExitStatus::OptError));
+    assert_eq!(prog.io.take_out(), "");
+    let cmd = cmd.opt(complete::FLAG);
+    prog.parse_from(&cmd, vec![complete::DYNAMIC, "--", "--v"]);
+    assert_eq!(prog.on_exit.status(), Some(ExitStatus::Ok));
+    assert_eq!(prog.io.take_out(), "--verbose\n");
+}
+
+#[test]
+fn test_opt_subcommand_help() {
+    use utils::{ExitStrategy,Output};
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    prog.on_exit.set_strategy(ExitStrategy::Return);
+    let cmd = Cmd::new("util").opt(HELP)
+        .subcommand(Cmd::new("add").about("Add a file").opt(HELP));
+    let m = prog.parse_from(&cmd, vec!["add", "--help"]);
+    assert!(m.subcommand().is_none());
+    assert_eq!(prog.on_exit.status(), Some(ExitStatus::Ok));
+    assert!(prog.io.take_out().contains("Usage: add [OPTIONS]\n\nAdd a file\n"));
+}
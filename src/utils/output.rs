@@ -0,0 +1,325 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+use utils::term;
+use utils::text;
+
+use std::fmt;
+use std::io::{self,Write};
+use std::sync::{Mutex,MutexGuard};
+use std::sync::atomic::{AtomicBool,Ordering};
+
+/// A destination for output written by rpf
+pub enum Stream {
+    /// The process's standard output
+    Stdout,
+    /// The process's standard error
+    Stderr,
+    /// An in-memory buffer, retrieved with `Output::take_out` and
+    /// `Output::take_err`
+    Buffer(Vec<u8>),
+    /// Any other writer
+    Writer(Box<dyn Write + Send>),
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Stream::Stdout => { io::stdout().write(buf) },
+            Stream::Stderr => { io::stderr().write(buf) },
+            Stream::Buffer(ref mut v) => { v.write(buf) },
+            Stream::Writer(ref mut w) => { w.write(buf) },
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Stream::Stdout => { io::stdout().flush() },
+            Stream::Stderr => { io::stderr().flush() },
+            Stream::Buffer(_) => { Ok(()) },
+            Stream::Writer(ref mut w) => { w.flush() },
+        }
+    }
+}
+
+/// Replaceable standard output and standard error streams that all rpf
+/// printing is routed through. The first error writing the output stream is
+/// kept for `Prog::exit` to report, and nothing more is written to it.
+///
+/// # Example
+///
+/// ```
+/// use rpf::Output;
+///
+/// let output = Output::capture();
+/// output.print(format_args!("util {}\n", "0.1.0"));
+/// output.eprint(format_args!("util: warning\n"));
+/// assert_eq!(output.take_out(), "util 0.1.0\n");
+/// assert_eq!(output.take_err(), "util: warning\n");
+/// ```
+pub struct Output {
+    out: Mutex<Stream>,
+    err: Mutex<Stream>,
+    failed: Mutex<Option<io::Error>>,
+    color: AtomicBool,
+}
+
+impl Output {
+    /// Creates an `Output` writing to the process's standard streams
+    pub const fn std() -> Output {
+        Output {
+            out: Mutex::new(Stream::Stdout),
+            err: Mutex::new(Stream::Stderr),
+            failed: Mutex::new(None),
+            color: AtomicBool::new(true),
+        }
+    }
+
+    /// Creates an `Output` writing both streams to in-memory buffers
+    pub const fn capture() -> Output {
+        Output {
+            out: Mutex::new(Stream::Buffer(Vec::new())),
+            err: Mutex::new(Stream::Buffer(Vec::new())),
+            failed: Mutex::new(None),
+            color: AtomicBool::new(true),
+        }
+    }
+
+    /// Replaces the output stream, returning the previous one
+    pub fn set_out(&self, stream: Stream) -> Stream {
+        let mut out = lock(&self.out);
+        let _ = out.flush();
+        ::std::mem::replace(&mut *out, stream)
+    }
+
+    /// Replaces the error stream, returning the previous one
+    pub fn set_err(&self, stream: Stream) -> Stream {
+        let mut err = lock(&self.err);
+        let _ = err.flush();
+        ::std::mem::replace(&mut *err, stream)
+    }
+
+    /// Sets whether text printed through this `Output` keeps its escape
+    /// codes, unlike `set_color` this does not affect other `Output`s
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::{Output,Styled};
+    ///
+    /// let output = Output::capture();
+    /// output.set_color(false);
+    /// output.print(format_args!("{}\n", "plain".bold()));
+    /// assert_eq!(output.take_out(), "plain\n");
+    /// ```
+    pub fn set_color(&self, enabled: bool) {
+        self.color.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Returns whether text printed through this `Output` keeps its escape
+    /// codes
+    pub fn color(&self) -> bool {
+        self.color.load(Ordering::SeqCst)
+    }
+
+    /// Writes formatted text to the output stream
+    pub fn print(&self, args: fmt::Arguments) {
+        let mut out = lock(&self.out);
+        if self.out_failed() { return; }
+        if let Err(e) = self.write_text(&mut out, args) {
+            self.fail(e);
+        }
+    }
+
+    /// Writes formatted text to the error stream
+    pub fn eprint(&self, args: fmt::Arguments) {
+        let _ = self.write_text(&mut lock(&self.err), args);
+    }
+
+    // Writes `args` to `stream`, without escape codes if colour is disabled
+    fn write_text(&self, stream: &mut Stream, args: fmt::Arguments) -> io::Result<()> {
+        if self.color() { return stream.write_fmt(args); }
+        stream.write_all(text::strip_ansi(&args.to_string()).as_bytes())
+    }
+
+    /// Writes bytes to the output stream unchanged, for output that need not
+    /// be text such as a child's
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::{Output,Stream};
+    ///
+    /// let output = Output::capture();
+    /// output.print_bytes(b"\xff\xfe\n");
+    /// match output.set_out(Stream::Stdout) {
+    ///     Stream::Buffer(v) => { assert_eq!(v, b"\xff\xfe\n"); },
+    ///     _ => { unreachable!(); },
+    /// }
+    /// ```
+    pub fn print_bytes(&self, buf: &[u8]) {
+        let mut out = lock(&self.out);
+        if self.out_failed() { return; }
+        if let Err(e) = out.write_all(buf) {
+            self.fail(e);
+        }
+    }
+
+    /// Writes bytes to the error stream unchanged
+    pub fn eprint_bytes(&self, buf: &[u8]) {
+        let _ = lock(&self.err).write_all(buf);
+    }
+
+    /// Flushes both streams
+    pub fn flush(&self) {
+        {
+            let mut out = lock(&self.out);
+            if !self.out_failed() {
+                if let Err(e) = out.flush() {
+                    self.fail(e);
+                }
+            }
+        }
+        let _ = lock(&self.err).flush();
+    }
+
+    /// Returns true if writing the output stream failed
+    pub fn out_failed(&self) -> bool {
+        lock_failed(&self.failed).is_some()
+    }
+
+    /// Returns the error that writing the output stream failed with, after
+    /// which the stream is written to again
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::{Output,Stream};
+    /// use std::io::{self,Write};
+    ///
+    /// struct Closed;
+    /// impl Write for Closed {
+    ///     fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+    ///         Err(io::Error::from(io::ErrorKind::BrokenPipe))
+    ///     }
+    ///     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// }
+    ///
+    /// let output = Output::capture();
+    /// output.set_out(Stream::Writer(Box::new(Closed)));
+    /// output.print(format_args!("lost\n"));
+    /// assert_eq!(output.take_out_error().unwrap().kind(), io::ErrorKind::BrokenPipe);
+    /// assert!(output.take_out_error().is_none());
+    /// ```
+    pub fn take_out_error(&self) -> Option<io::Error> {
+        lock_failed(&self.failed).take()
+    }
+
+    fn fail(&self, e: io::Error) {
+        let mut failed = lock_failed(&self.failed);
+        if failed.is_none() {
+            *failed = Some(e);
+        }
+    }
+
+    /// Returns true if the output stream is standard output and it is a
+    /// terminal
+    pub fn out_is_tty(&self) -> bool {
+        matches!(*lock(&self.out), Stream::Stdout) && term::is_tty(term::STDOUT)
+    }
+
+    // Returns true if the output stream is standard output
+    pub(crate) fn out_is_stdout(&self) -> bool {
+        matches!(*lock(&self.out), Stream::Stdout)
+    }
+
+    /// Returns true if the error stream is standard error and it is a terminal
+    pub fn err_is_tty(&self) -> bool {
+        matches!(*lock(&self.err), Stream::Stderr) && term::is_tty(term::STDERR)
+    }
+
+    /// Drains and returns everything captured on the output stream, returns an
+    /// empty string if the output stream is not a `Stream::Buffer`
+    pub fn take_out(&self) -> String {
+        take(&mut lock(&self.out))
+    }
+
+    /// Drains and returns everything captured on the error stream, returns an
+    /// empty string if the error stream is not a `Stream::Buffer`
+    pub fn take_err(&self) -> String {
+        take(&mut lock(&self.err))
+    }
+}
+
+impl Default for Output {
+    fn default() -> Output {
+        Output::std()
+    }
+}
+
+// Output remains usable after a panic while printing
+fn lock(stream: &Mutex<Stream>) -> MutexGuard<'_, Stream> {
+    match stream.lock() {
+        Ok(guard) => { guard },
+        Err(poisoned) => { poisoned.into_inner() },
+    }
+}
+
+fn lock_failed(failed: &Mutex<Option<io::Error>>) -> MutexGuard<'_, Option<io::Error>> {
+    match failed.lock() {
+        Ok(guard) => { guard },
+        Err(poisoned) => { poisoned.into_inner() },
+    }
+}
+
+fn take(stream: &mut Stream) -> String {
+    match *stream {
+        Stream::Buffer(ref mut v) => {
+            String::from_utf8_lossy(&::std::mem::take(v)).into_owned()
+        },
+        _ => { String::new() },
+    }
+}
+
+#[test]
+fn test_output_capture() {
+    let output = Output::capture();
+    output.print(format_args!("{} {}\n", "util", "0.1.0"));
+    output.eprint(format_args!("error\n"));
+    assert_eq!(output.take_out(), "util 0.1.0\n");
+    assert_eq!(output.take_out(), "");
+    assert_eq!(output.take_err(), "error\n");
+}
+
+#[test]
+fn test_output_set_out() {
+    let output = Output::std();
+    output.set_out(Stream::Buffer(Vec::new()));
+    output.print(format_args!("captured"));
+    assert_eq!(output.take_out(), "captured");
+    assert_eq!(output.take_err(), "");
+}
+
+#[test]
+fn test_output_write_error() {
+    use utils::{ExitStatus,ExitStrategy,Prog};
+    use utils::signal::BROKEN_PIPE;
+
+    struct Failing(io::ErrorKind);
+    impl Write for Failing {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> { Err(io::Error::from(self.0)) }
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    prog.on_exit.set_strategy(ExitStrategy::Return);
+    prog.io.set_out(Stream::Writer(Box::new(Failing(io::ErrorKind::BrokenPipe))));
+    prog.copyright("Copyright", &["Author"]);
+    prog.exit(ExitStatus::Ok);
+    assert_eq!(prog.on_exit.status(), Some(BROKEN_PIPE));
+    assert_eq!(prog.io.take_err(), "");
+    prog.io.set_out(Stream::Writer(Box::new(Failing(io::ErrorKind::WriteZero))));
+    prog.io.print(format_args!("one\n"));
+    prog.io.print(format_args!("two\n"));
+    prog.check_output();
+    assert_eq!(prog.on_exit.status(), Some(ExitStatus::Error));
+    assert_eq!(prog.io.take_err().matches("write error").count(), 1);
+}
@@ -0,0 +1,242 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Piping long output through a pager
+//!
+//! `Prog::pager` holds back what the program prints until it is longer than
+//! the terminal, then starts the pager and sends everything to it. Output
+//! that fits on the screen is printed as usual, as is all output when the
+//! pager cannot be started. The pager is `<NAME>_PAGER`, then `PAGER`, then
+//! `less -FRX`; an empty value or `cat` turns paging off.
+//!
+//! ```no_run
+//! use rpf::{Cmd,Prog,pager};
+//!
+//! let prog = Prog::new("util", "0.1.0", "2015");
+//! let m = prog.parse_args(&Cmd::new("util").opt(pager::NO_PAGER));
+//! let _pager = prog.pager(&m);
+//! for i in 0..1000 {
+//!     prog.io.print(format_args!("line {}\n", i));
+//! }
+//! ```
+
+use utils::{Matches,Opt,Prog,Stream};
+use utils::args;
+use utils::config::env_prefix;
+use utils::term;
+
+use std::env;
+use std::io::{self,Write};
+use std::process::{Child,ChildStdin,Command,Stdio};
+use std::sync::{Arc,Mutex,MutexGuard};
+
+/// Option that stops `Prog::pager` from starting a pager
+pub static NO_PAGER: Opt = Opt::flag("no-pager", "Do not pipe output into a pager");
+
+/// Pager used when neither `<NAME>_PAGER` nor `PAGER` is set
+pub const DEFAULT: &str = "less -FRX";
+
+struct State {
+    cmd: Vec<String>,
+    limit: usize,
+    buf: Vec<u8>,
+    lines: usize,
+    child: Option<Child>,
+    stdin: Option<ChildStdin>,
+    fallback: Option<Stream>,
+    direct: bool,
+}
+
+impl State {
+    // Starts the pager and sends it the held back output, printing it to the
+    // original stream instead if the pager cannot be started
+    fn spawn(&mut self) -> io::Result<()> {
+        self.direct = true;
+        let buf = ::std::mem::take(&mut self.buf);
+        let mut child = match Command::new(&self.cmd[0]).args(&self.cmd[1..])
+            .stdin(Stdio::piped()).spawn() {
+            Ok(c) => { c },
+            Err(_) => { return self.write_fallback(&buf); },
+        };
+        let mut stdin = child.stdin.take();
+        self.child = Some(child);
+        if let Some(ref mut s) = stdin {
+            s.write_all(&buf)?;
+        }
+        self.stdin = stdin;
+        Ok(())
+    }
+
+    fn write_fallback(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self.fallback {
+            Some(ref mut s) => { s.write_all(buf) },
+            None => { io::stdout().write_all(buf) },
+        }
+    }
+
+    // Prints held back output or waits for the pager to be closed
+    fn finish(&mut self) {
+        if !self.direct {
+            self.direct = true;
+            let buf = ::std::mem::take(&mut self.buf);
+            let _ = self.write_fallback(&buf);
+        }
+        self.stdin = None;
+        if let Some(mut child) = self.child.take() {
+            let _ = child.wait();
+        }
+        if let Some(ref mut s) = self.fallback {
+            let _ = s.flush();
+        }
+    }
+}
+
+struct Pipe(Arc<Mutex<State>>);
+
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = lock(&self.0);
+        if let Some(ref mut s) = state.stdin {
+            return s.write(buf);
+        }
+        if state.direct || state.child.is_some() {
+            state.write_fallback(buf)?;
+            return Ok(buf.len());
+        }
+        state.buf.extend_from_slice(buf);
+        state.lines += buf.iter().filter(|&&b| b == b'\n').count();
+        if state.lines >= state.limit {
+            state.spawn()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match lock(&self.0).stdin {
+            Some(ref mut s) => { s.flush() },
+            None => { Ok(()) },
+        }
+    }
+}
+
+/// Output being sent to a pager, created by `Prog::pager`. Dropping it waits
+/// for the pager to be closed and restores the original output stream.
+pub struct Pager<'a> {
+    prog: &'a Prog,
+    state: Arc<Mutex<State>>,
+}
+
+impl<'a> Drop for Pager<'a> {
+    fn drop(&mut self) {
+        self.prog.io.flush();
+        let fallback = {
+            let mut state = lock(&self.state);
+            state.finish();
+            state.fallback.take()
+        };
+        if let Some(s) = fallback {
+            self.prog.io.set_out(s);
+        }
+    }
+}
+
+/// Returns the pager command for the program `name`, `None` if paging is
+/// turned off
+///
+/// # Example
+/// ```
+/// use rpf::pager;
+///
+/// std::env::set_var("UTIL_PAGER", "more -s");
+/// assert_eq!(pager::command("util"), Some(vec!["more".to_string(), "-s".to_string()]));
+/// std::env::set_var("UTIL_PAGER", "");
+/// assert_eq!(pager::command("util"), None);
+/// ```
+pub fn command(name: &str) -> Option<Vec<String>> {
+    let line = env::var(format!("{}_PAGER", env_prefix(name)))
+        .or_else(|_| env::var("PAGER"))
+        .unwrap_or_else(|_| DEFAULT.to_string());
+    match args::split(&line) {
+        Ok(ref cmd) if cmd.is_empty() || cmd[0] == "cat" => { None },
+        Ok(cmd) => { Some(cmd) },
+        Err(_) => { None },
+    }
+}
+
+impl Prog {
+    /// Sends output to a pager once it is longer than the terminal, if the
+    /// output stream is a terminal and `NO_PAGER` was not given. Returns
+    /// `None` when no pager is used.
+    pub fn pager(&self, m: &Matches) -> Option<Pager<'_>> {
+        if m.has(NO_PAGER.long) || !self.io.out_is_tty() { return None; }
+        let cmd = command(self.name)?;
+        Some(self.pager_with(cmd, term::height().saturating_sub(1).max(1)))
+    }
+
+    // Sends output to `cmd` once `limit` lines have been printed
+    fn pager_with(&self, cmd: Vec<String>, limit: usize) -> Pager<'_> {
+        let state = Arc::new(Mutex::new(State {
+            cmd, limit, buf: Vec::new(), lines: 0, child: None, stdin: None,
+            fallback: None, direct: false,
+        }));
+        let fallback = self.io.set_out(Stream::Writer(Box::new(Pipe(state.clone()))));
+        lock(&state).fallback = Some(fallback);
+        // The hook does nothing once the `Pager` is dropped, so that paging
+        // several times keeps no state alive until exit
+        let hook = Arc::downgrade(&state);
+        self.on_exit.hook(move || {
+            if let Some(state) = hook.upgrade() { lock(&state).finish(); }
+        });
+        Pager { prog: self, state }
+    }
+}
+
+// The pager remains usable after a panic while printing
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    match state.lock() {
+        Ok(guard) => { guard },
+        Err(poisoned) => { poisoned.into_inner() },
+    }
+}
+
+#[test]
+fn test_pager_short_output() {
+    use utils::Output;
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    let pager = prog.pager_with(vec!["rpf-no-such-pager".to_string()], 3);
+    let state = Arc::downgrade(&pager.state);
+    prog.io.print(format_args!("one\ntwo\n"));
+    assert_eq!(prog.io.take_out(), "");
+    drop(pager);
+    assert_eq!(prog.io.take_out(), "one\ntwo\n");
+    assert!(state.upgrade().is_none());
+}
+
+#[test]
+fn test_pager_spawn_failure() {
+    use utils::Output;
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    let pager = prog.pager_with(vec!["rpf-no-such-pager".to_string()], 2);
+    prog.io.print(format_args!("1\n2\n3\n"));
+    drop(pager);
+    assert_eq!(prog.io.take_out(), "1\n2\n3\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_pager_long_output() {
+    use std::fs;
+    use utils::Output;
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    let cmd = args::split("sh -c 'cat > test-pager.out'").unwrap();
+    let pager = prog.pager_with(cmd, 2);
+    prog.io.print(format_args!("1\n2\n3\n"));
+    drop(pager);
+    assert_eq!(prog.io.take_out(), "");
+    assert_eq!(fs::read_to_string("test-pager.out").unwrap(), "1\n2\n3\n");
+    fs::remove_file("test-pager.out").unwrap();
+}
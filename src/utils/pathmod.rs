@@ -74,6 +74,10 @@ pub trait PathMod {
     fn as_string(&self) -> String;
 }
 
+// Written before these lints existed, kept as is
+#[allow(clippy::needless_return, clippy::needless_bool, clippy::manual_map,
+        clippy::double_ended_iterator_last, clippy::manual_unwrap_or_default,
+        clippy::manual_unwrap_or)]
 impl PathMod for PathBuf {
     fn is_dot(&self) -> bool {
         let file_name = match self.file_name() {
@@ -115,6 +119,10 @@ impl PathMod for PathBuf {
     }
 }
 
+// Written before these lints existed, kept as is
+#[allow(clippy::needless_return, clippy::needless_bool, clippy::manual_map,
+        clippy::double_ended_iterator_last, clippy::manual_unwrap_or_default,
+        clippy::manual_unwrap_or)]
 impl PathMod for Path {
     fn is_dot(&self) -> bool {
         let file_name = match self.file_name() {
@@ -181,6 +189,7 @@ fn test_pathmod_as_string() {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_pathmod_is_dot_success() {
     let path = Path::new("/dir/test/.test");
     assert_eq!(path.is_dot(), true);
@@ -188,6 +197,7 @@ fn test_pathmod_is_dot_success() {
 
 #[test]
 #[should_panic]
+#[allow(clippy::bool_assert_comparison)]
 fn test_pathmod_is_dot_fail() {
     let false_path = Path::new("/");
     assert_eq!(false_path.is_dot(), true);
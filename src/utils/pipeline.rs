@@ -0,0 +1,362 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Pipelines of processes, without a shell
+//!
+//! Each stage's standard output is connected to the next stage's standard
+//! input. The first stage reads what its `Process::stdin` is set to, and the
+//! last stage's output can be captured, streamed or redirected to a file.
+//! Like `set -o pipefail` in bash, a pipeline fails if any stage fails, with
+//! the status of the last stage that failed. Timeouts set on stages are not
+//! used.
+//!
+//! ```
+//! use rpf::pipeline::Pipeline;
+//! use rpf::process::Process;
+//!
+//! let words = Process::new("printf").arg("b\\na\\nb\\n");
+//! let done = Pipeline::new(words).pipe(Process::new("sort")).pipe(Process::new("uniq"))
+//!     .output().unwrap();
+//! assert!(done.success());
+//! assert_eq!(done.stdout_str(), "a\nb\n");
+//! ```
+
+use utils::{Exit,ExitStatus,Output,Prog};
+use utils::args;
+use utils::process::{self,Input,Process,ProcessError,collect};
+
+use std::fs::{File,OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Child,ChildStdout,Stdio};
+use std::thread;
+
+/// Where the last stage's output or every stage's errors go
+#[derive(Clone,Debug,PartialEq)]
+pub enum Sink {
+    /// Captured by `Pipeline::output` or printed by `Pipeline::stream`
+    Capture,
+    /// Discarded, like `> /dev/null`
+    Null,
+    /// Written to a file, replacing it, like `> file`
+    File(PathBuf),
+    /// Added to the end of a file, like `>> file`
+    Append(PathBuf),
+}
+
+impl Sink {
+    // Opens the file the sink writes to, `None` if it is not a file
+    fn open(&self) -> Result<Option<File>, ProcessError> {
+        let (path, append) = match *self {
+            Sink::Capture | Sink::Null => { return Ok(None); },
+            Sink::File(ref path) => { (path, false) },
+            Sink::Append(ref path) => { (path, true) },
+        };
+        OpenOptions::new().write(true).create(true).append(append).truncate(!append)
+            .open(path).map(Some)
+            .map_err(|e| ProcessError::Open(path.clone(), e))
+    }
+
+    // Returns where a stage writes to, sharing the file opened by `open` so
+    // that stages writing to it do not overwrite each other
+    fn stdio(&self, file: &Option<File>) -> Result<Stdio, ProcessError> {
+        match (self, file) {
+            (&Sink::File(ref path), &Some(ref f)) | (&Sink::Append(ref path), &Some(ref f)) => {
+                f.try_clone().map(Stdio::from).map_err(|e| ProcessError::Open(path.clone(), e))
+            },
+            (&Sink::Null, _) => { Ok(Stdio::null()) },
+            _ => { Ok(Stdio::piped()) },
+        }
+    }
+
+    // How the redirection is written in a shell, after `op`
+    fn shell(&self, op: &str) -> String {
+        match *self {
+            Sink::Capture => { String::new() },
+            Sink::Null => { format!(" {}> /dev/null", op) },
+            Sink::File(ref path) => {
+                format!(" {}> {}", op, args::quote(&path.to_string_lossy()))
+            },
+            Sink::Append(ref path) => {
+                format!(" {}>> {}", op, args::quote(&path.to_string_lossy()))
+            },
+        }
+    }
+}
+
+/// Processes connected output to input, like `a | b | c` in a shell
+#[derive(Clone,Debug)]
+pub struct Pipeline {
+    stages: Vec<Process>,
+    stdout: Sink,
+    stderr: Sink,
+}
+
+impl Process {
+    /// Starts a pipeline sending this process's output to `next`
+    pub fn pipe(self, next: Process) -> Pipeline {
+        Pipeline::new(self).pipe(next)
+    }
+}
+
+impl Pipeline {
+    /// Creates a pipeline of one stage
+    pub fn new(first: Process) -> Pipeline {
+        Pipeline { stages: vec![first], stdout: Sink::Capture, stderr: Sink::Capture }
+    }
+
+    /// Adds a stage reading the output of the last one
+    pub fn pipe(mut self, next: Process) -> Pipeline {
+        self.stages.push(next);
+        self
+    }
+
+    /// Sets where the last stage's standard output goes
+    pub fn stdout(mut self, sink: Sink) -> Pipeline {
+        self.stdout = sink;
+        self
+    }
+
+    /// Sets where the standard error of every stage goes
+    pub fn stderr(mut self, sink: Sink) -> Pipeline {
+        self.stderr = sink;
+        self
+    }
+
+    /// Returns the pipeline as it would be typed in a shell. Standard error
+    /// of every stage goes to one redirection, which is written as a group.
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::pipeline::{Pipeline,Sink};
+    /// use rpf::process::{Input,Process};
+    ///
+    /// let p = Process::new("grep").arg("a b").stdin(Input::File("in.txt".into()))
+    ///     .pipe(Process::new("wc").arg("-l"))
+    ///     .stdout(Sink::Append("count".into())).stderr(Sink::Null);
+    /// assert_eq!(p.command_line(), "{ grep 'a b' < in.txt | wc -l >> count; } 2> /dev/null");
+    /// ```
+    pub fn command_line(&self) -> String {
+        let mut line = String::new();
+        for (i, stage) in self.stages.iter().enumerate() {
+            if i > 0 { line.push_str(" | "); }
+            line.push_str(&stage.command_line());
+            if i == 0 {
+                match stage.stdin {
+                    Input::Null => { line.push_str(" < /dev/null"); },
+                    Input::File(ref path) => {
+                        line.push_str(&format!(" < {}", args::quote(&path.to_string_lossy())));
+                    },
+                    _ => {},
+                }
+            }
+        }
+        line.push_str(&self.stdout.shell(""));
+        match self.stderr {
+            Sink::Capture => { line },
+            _ => { format!("{{ {}; }}{}", line, self.stderr.shell("2")) },
+        }
+    }
+
+    /// Runs the pipeline, capturing the last stage's standard output and the
+    /// standard error of every stage unless they are redirected
+    pub fn output(&self) -> Result<Piped, ProcessError> {
+        self.execute(None)
+    }
+
+    /// Runs the pipeline, printing the last stage's standard output and the
+    /// standard error of every stage through `io` unless they are redirected
+    pub fn stream(&self, io: &Output) -> Result<Piped, ProcessError> {
+        self.execute(Some(io))
+    }
+
+    fn execute(&self, io: Option<&Output>) -> Result<Piped, ProcessError> {
+        let out_file = self.stdout.open()?;
+        let err_file = self.stderr.open()?;
+        let mut children: Vec<Child> = Vec::new();
+        let mut stderrs = Vec::new();
+        let mut prev: Option<ChildStdout> = None;
+        for (i, stage) in self.stages.iter().enumerate() {
+            let last = i + 1 == self.stages.len();
+            let mut cmd = stage.command();
+            let spawned = (|| {
+                cmd.stdin(match prev.take() {
+                    Some(pipe) => { Stdio::from(pipe) },
+                    None => { stage.stdin.stdio()? },
+                });
+                cmd.stdout(if last { self.stdout.stdio(&out_file)? } else { Stdio::piped() });
+                cmd.stderr(self.stderr.stdio(&err_file)?);
+                cmd.spawn().map_err(|e| ProcessError::Spawn(stage.command_line(), e))
+            })();
+            let mut child = match spawned {
+                Ok(child) => { child },
+                Err(e) => {
+                    // Stages already started are stopped rather than left
+                    // waiting for input that never comes
+                    for mut child in children.into_iter() {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                    }
+                    return Err(e);
+                },
+            };
+            if !last { prev = child.stdout.take(); }
+            stderrs.push(child.stderr.take());
+            children.push(child);
+        }
+        let first_stdin = children[0].stdin.take();
+        let stdout = children.last_mut().and_then(|c| c.stdout.take());
+        let line = self.command_line();
+        thread::scope(|scope| {
+            if let (Some(mut pipe), Input::Bytes(bytes)) = (first_stdin, &self.stages[0].stdin) {
+                scope.spawn(move || { let _ = pipe.write_all(bytes); });
+            }
+            let out = scope.spawn(move || collect(stdout, io, false));
+            let errs: Vec<_> = stderrs.into_iter()
+                .map(|pipe| scope.spawn(move || collect(pipe, io, true)))
+                .collect();
+            let mut stages = Vec::new();
+            for child in children.iter_mut() {
+                match child.wait() {
+                    Ok(status) => { stages.push(status); },
+                    Err(e) => { return Err(ProcessError::Spawn(line.clone(), e)); },
+                }
+            }
+            let mut stderr = Vec::new();
+            for err in errs {
+                stderr.extend(err.join().unwrap_or_default());
+            }
+            Ok(Piped { stages, stdout: out.join().unwrap_or_default(), stderr })
+        })
+    }
+}
+
+/// A pipeline whose stages have all exited
+#[derive(Debug)]
+pub struct Piped {
+    /// Exit status of each stage, in order
+    pub stages: Vec<::std::process::ExitStatus>,
+    /// Captured standard output of the last stage
+    pub stdout: Vec<u8>,
+    /// Captured standard error of every stage, in stage order
+    pub stderr: Vec<u8>,
+}
+
+impl Piped {
+    /// Returns the index of the last stage that failed
+    pub fn failed_stage(&self) -> Option<usize> {
+        self.stages.iter().rposition(|s| !s.success())
+    }
+
+    /// Returns true if every stage succeeded
+    pub fn success(&self) -> bool {
+        self.failed_stage().is_none()
+    }
+
+    /// Returns the status of the last stage that failed as an `ExitStatus`,
+    /// `ExitStatus::Ok` if none did
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::ExitStatus;
+    /// use rpf::process::Process;
+    ///
+    /// let done = Process::new("false").pipe(Process::new("true")).output().unwrap();
+    /// assert_eq!(done.failed_stage(), Some(0));
+    /// assert_eq!(done.exit_status(), ExitStatus::Error);
+    /// ```
+    pub fn exit_status(&self) -> ExitStatus {
+        match self.failed_stage() {
+            Some(i) => { process::exit_status(&self.stages[i]) },
+            None => { ExitStatus::Ok },
+        }
+    }
+
+    /// Returns the last stage's standard output as text
+    pub fn stdout_str(&self) -> String {
+        String::from_utf8_lossy(&self.stdout).into_owned()
+    }
+
+    /// Returns standard error as text
+    pub fn stderr_str(&self) -> String {
+        String::from_utf8_lossy(&self.stderr).into_owned()
+    }
+}
+
+impl Prog {
+    /// Runs `pipeline` with its output printed through `io`, exiting with
+    /// `Exit::error` naming the failed stage if any stage fails
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::{ExitStatus,ExitStrategy,Output,Prog};
+    /// use rpf::process::Process;
+    ///
+    /// let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    /// prog.on_exit.set_strategy(ExitStrategy::Return);
+    /// assert!(prog.run_pipeline(&Process::new("false").pipe(Process::new("cat"))).is_none());
+    /// assert!(prog.io.take_err().contains("`false` failed with exit status 1 in `false | cat`"));
+    /// ```
+    pub fn run_pipeline(&self, pipeline: &Pipeline) -> Option<Piped> {
+        match pipeline.stream(&self.io) {
+            Ok(done) => {
+                let i = match done.failed_stage() {
+                    Some(i) => { i },
+                    None => { return Some(done); },
+                };
+                let e = ProcessError::Failed(pipeline.stages[i].command_line(),
+                                             done.exit_status());
+                self.error(format!("{} in `{}`", e, pipeline.command_line()), e.status());
+            },
+            Err(e) => { self.error(e.to_string(), e.status()); },
+        }
+        None
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn test_pipeline_redirects() {
+    use std::fs;
+    use utils::test;
+
+    fs::create_dir_all("test-pipeline").unwrap();
+    fs::write("test-pipeline/in", "b\na\nb\n").unwrap();
+    let sort = Process::new("sort").stdin(Input::File("test-pipeline/in".into()));
+    let uniq = sort.pipe(Process::new("uniq").arg("-c"))
+        .pipe(Process::new("sh").args(&["-c", "cat; echo noise >&2"]));
+    let out = uniq.clone().stdout(Sink::File("test-pipeline/out".into())).stderr(Sink::Null);
+    assert!(out.output().unwrap().success());
+    let errors = Process::new("sh").args(&["-c", "echo first-stage-error >&2"])
+        .pipe(Process::new("sh").args(&["-c", "sleep 0.1; echo 2 >&2"]))
+        .stderr(Sink::File("test-pipeline/err".into()));
+    assert!(errors.output().unwrap().success());
+    assert_eq!(fs::read_to_string("test-pipeline/err").unwrap(), "first-stage-error\n2\n");
+    let appended = uniq.stdout(Sink::Append("test-pipeline/out".into()));
+    let done = appended.output().unwrap();
+    assert_eq!(done.stdout_str(), "");
+    assert_eq!(done.stderr_str(), "noise\n");
+    let counts = fs::read_to_string("test-pipeline/out").unwrap();
+    assert_eq!(counts.split_whitespace().collect::<Vec<_>>(), ["1", "a", "2", "b"].repeat(2));
+    let missing = Process::new("cat").stdin(Input::File("test-pipeline/none".into()))
+        .pipe(Process::new("cat")).output().unwrap_err();
+    assert!(missing.to_string().starts_with("Cannot open 'test-pipeline/none': "));
+    test::remove("test-pipeline");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_pipeline_pipefail() {
+    let done = Process::new("sh").args(&["-c", "exit 3"]).pipe(Process::new("false"))
+        .pipe(Process::new("cat")).output().unwrap();
+    assert_eq!(done.stages.iter().map(|s| s.code()).collect::<Vec<_>>(),
+               vec![Some(3), Some(1), Some(0)]);
+    assert_eq!(done.failed_stage(), Some(1));
+    let head = Process::new("yes").pipe(Process::new("head").arg("-n2")).output().unwrap();
+    assert_eq!(head.stdout_str(), "y\ny\n");
+    assert_eq!(head.exit_status(), ExitStatus::Signal(13));
+    let err = Process::new("sleep").arg("5").pipe(Process::new("rpf-no-such-program"))
+        .output().unwrap_err();
+    assert!(err.to_string().starts_with("Cannot run `rpf-no-such-program`: "));
+}
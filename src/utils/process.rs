@@ -0,0 +1,506 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Running other programs
+//!
+//! A `Process` describes a command to run. Its output is either captured with
+//! `Process::output` or passed on line by line to an `Output` with
+//! `Process::stream`, so that it goes wherever the program's own output goes.
+//! `Prog::run` and `Prog::run_output` also report failures with `Exit::error`,
+//! showing the command line quoted as it would be typed in a shell.
+//!
+//! ```
+//! use rpf::process::Process;
+//!
+//! let done = Process::new("echo").args(&["hello", "world"]).output().unwrap();
+//! assert!(done.success());
+//! assert_eq!(done.stdout_str(), "hello world\n");
+//! ```
+
+#[cfg(unix)]
+extern crate libc;
+
+use utils::{Exit,ExitStatus,Output,Prog};
+use utils::args;
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self,BufRead,BufReader,Read,Write};
+use std::path::PathBuf;
+use std::process::{self,Child,Command,Stdio};
+use std::thread;
+use std::time::{Duration,Instant};
+
+// How often a child with a timeout is checked on
+const POLL: Duration = Duration::from_millis(10);
+
+/// Time a child is given to exit after its timeout before it is killed
+pub const KILL_AFTER: Duration = Duration::from_secs(5);
+
+/// Where a child's standard input comes from
+#[derive(Clone,Debug,PartialEq)]
+pub enum Input {
+    /// The program's own standard input
+    Inherit,
+    /// Nothing, reading gives end of file at once
+    Null,
+    /// The given bytes
+    Bytes(Vec<u8>),
+    /// The contents of a file
+    File(PathBuf),
+}
+
+impl Input {
+    pub(crate) fn stdio(&self) -> Result<Stdio, ProcessError> {
+        match *self {
+            Input::Inherit => { Ok(Stdio::inherit()) },
+            Input::Null => { Ok(Stdio::null()) },
+            Input::Bytes(_) => { Ok(Stdio::piped()) },
+            Input::File(ref path) => {
+                File::open(path).map(Stdio::from)
+                    .map_err(|e| ProcessError::Open(path.clone(), e))
+            },
+        }
+    }
+}
+
+/// Reasons running a child failed
+#[derive(Debug)]
+pub enum ProcessError {
+    /// The child could not be started
+    Spawn(String, io::Error),
+    /// A file to redirect to or from could not be opened
+    Open(PathBuf, io::Error),
+    /// The child ran longer than its timeout and was stopped
+    TimedOut(String, Duration),
+    /// The child exited unsuccessfully
+    Failed(String, ExitStatus),
+}
+
+impl ProcessError {
+    /// Returns the status the program exits with for this error, the child's
+    /// own status if it was ended by a signal and `ExitStatus::Error`
+    /// otherwise
+    pub fn status(&self) -> ExitStatus {
+        match *self {
+            ProcessError::Failed(_, status @ ExitStatus::Signal(_)) => { status },
+            _ => { ExitStatus::Error },
+        }
+    }
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProcessError::Spawn(ref cmd, ref e) => { write!(f, "Cannot run `{}`: {}", cmd, e) },
+            ProcessError::Open(ref path, ref e) => {
+                write!(f, "Cannot open '{}': {}", path.display(), e)
+            },
+            ProcessError::TimedOut(ref cmd, t) => {
+                write!(f, "`{}` timed out after {:?}", cmd, t)
+            },
+            ProcessError::Failed(ref cmd, ExitStatus::Signal(n)) => {
+                write!(f, "`{}` was terminated by signal {}", cmd, n)
+            },
+            ProcessError::Failed(ref cmd, status) => {
+                write!(f, "`{}` failed with exit status {}", cmd, status.code())
+            },
+        }
+    }
+}
+
+impl Error for ProcessError {}
+
+/// A child that has exited
+#[derive(Debug)]
+pub struct Completed {
+    /// Exit status reported by the operating system
+    pub status: process::ExitStatus,
+    /// Captured standard output, empty when streamed
+    pub stdout: Vec<u8>,
+    /// Captured standard error, empty when streamed
+    pub stderr: Vec<u8>,
+}
+
+impl Completed {
+    /// Returns true if the child exited with status 0
+    pub fn success(&self) -> bool {
+        self.status.success()
+    }
+
+    /// Returns the child's status as an `ExitStatus`: `Ok` for 0,
+    /// `Signal` if a signal ended it and `Error` for any other code
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::ExitStatus;
+    /// use rpf::process::Process;
+    ///
+    /// let done = Process::new("sh").args(&["-c", "exit 3"]).output().unwrap();
+    /// assert_eq!(done.exit_status(), ExitStatus::Error);
+    /// assert_eq!(done.status.code(), Some(3));
+    /// ```
+    pub fn exit_status(&self) -> ExitStatus {
+        exit_status(&self.status)
+    }
+
+    /// Returns standard output as text
+    pub fn stdout_str(&self) -> String {
+        String::from_utf8_lossy(&self.stdout).into_owned()
+    }
+
+    /// Returns standard error as text
+    pub fn stderr_str(&self) -> String {
+        String::from_utf8_lossy(&self.stderr).into_owned()
+    }
+}
+
+/// Maps an operating system exit status to an `ExitStatus`
+#[cfg(unix)]
+pub fn exit_status(status: &process::ExitStatus) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+
+    match (status.code(), status.signal()) {
+        (Some(0), _) => { ExitStatus::Ok },
+        (_, Some(sig)) => { ExitStatus::Signal(sig) },
+        _ => { ExitStatus::Error },
+    }
+}
+
+/// Maps an operating system exit status to an `ExitStatus`
+#[cfg(not(unix))]
+pub fn exit_status(status: &process::ExitStatus) -> ExitStatus {
+    if status.success() { ExitStatus::Ok } else { ExitStatus::Error }
+}
+
+/// A command to run, built up like `std::process::Command`
+///
+/// # Example
+/// ```
+/// use rpf::process::Process;
+/// use std::time::Duration;
+///
+/// let sleep = Process::new("sleep").arg("10").timeout(Duration::from_millis(50));
+/// assert_eq!(sleep.output().unwrap_err().to_string(), "`sleep 10` timed out after 50ms");
+/// ```
+#[derive(Clone,Debug)]
+pub struct Process {
+    program: String,
+    args: Vec<String>,
+    env: Vec<(String, Option<String>)>,
+    cwd: Option<PathBuf>,
+    pub(crate) stdin: Input,
+    timeout: Option<Duration>,
+    kill_after: Duration,
+}
+
+impl Process {
+    /// Creates a command running `program` with no arguments, which inherits
+    /// the environment, working directory and standard input
+    pub fn new<T: Into<String>>(program: T) -> Process {
+        Process { program: program.into(), args: Vec::new(), env: Vec::new(), cwd: None,
+                  stdin: Input::Inherit, timeout: None, kill_after: KILL_AFTER }
+    }
+
+    /// Adds an argument
+    pub fn arg<T: Into<String>>(mut self, arg: T) -> Process {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Adds several arguments
+    pub fn args<T: AsRef<str>>(mut self, args: &[T]) -> Process {
+        self.args.extend(args.iter().map(|a| a.as_ref().to_string()));
+        self
+    }
+
+    /// Sets an environment variable for the child
+    pub fn env<K: Into<String>, V: Into<String>>(mut self, key: K, val: V) -> Process {
+        self.env.push((key.into(), Some(val.into())));
+        self
+    }
+
+    /// Removes an environment variable for the child
+    pub fn env_remove<K: Into<String>>(mut self, key: K) -> Process {
+        self.env.push((key.into(), None));
+        self
+    }
+
+    /// Sets the child's working directory
+    pub fn current_dir<P: Into<PathBuf>>(mut self, dir: P) -> Process {
+        self.cwd = Some(dir.into());
+        self
+    }
+
+    /// Sets where the child's standard input comes from
+    pub fn stdin(mut self, input: Input) -> Process {
+        self.stdin = input;
+        self
+    }
+
+    /// Stops the child if it runs longer than `timeout`. It is asked to exit
+    /// with `SIGTERM` first and killed if it is still running after the time
+    /// set with `kill_after`. On Unix the child runs in a process group of its
+    /// own, and the whole group is stopped. Such a child cannot read the
+    /// terminal, so `Input::Inherit` is replaced with `Input::Null`.
+    pub fn timeout(mut self, timeout: Duration) -> Process {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long a child is given to exit after its timeout, `KILL_AFTER`
+    /// by default
+    pub fn kill_after(mut self, grace: Duration) -> Process {
+        self.kill_after = grace;
+        self
+    }
+
+    /// Returns the command line, quoted as it would be typed in a shell
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::process::Process;
+    ///
+    /// let cmd = Process::new("grep").args(&["-r", "two words", "."]);
+    /// assert_eq!(cmd.command_line(), "grep -r 'two words' .");
+    /// ```
+    pub fn command_line(&self) -> String {
+        let mut words = vec![&self.program[..]];
+        words.extend(self.args.iter().map(|a| &a[..]));
+        args::join(&words)
+    }
+
+    /// Runs the command, capturing its standard output and standard error.
+    /// An unsuccessful exit is not an error, see `Completed::success`.
+    pub fn output(&self) -> Result<Completed, ProcessError> {
+        self.execute(None)
+    }
+
+    /// Runs the command, printing its standard output and standard error
+    /// through `io` as each line arrives
+    pub fn stream(&self, io: &Output) -> Result<Completed, ProcessError> {
+        self.execute(Some(io))
+    }
+
+    pub(crate) fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(&self.args);
+        for (key, val) in self.env.iter() {
+            match *val {
+                Some(ref val) => { cmd.env(key, val); },
+                None => { cmd.env_remove(key); },
+            }
+        }
+        if let Some(ref dir) = self.cwd {
+            cmd.current_dir(dir);
+        }
+        cmd
+    }
+
+    fn execute(&self, io: Option<&Output>) -> Result<Completed, ProcessError> {
+        let mut cmd = self.command();
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        match self.stdin {
+            // Reading the terminal from another process group stops the child
+            Input::Inherit if self.timeout.is_some() => { cmd.stdin(Stdio::null()); },
+            ref input => { cmd.stdin(input.stdio()?); },
+        }
+        if self.timeout.is_some() {
+            own_group(&mut cmd);
+        }
+        let mut child = cmd.spawn()
+            .map_err(|e| ProcessError::Spawn(self.command_line(), e))?;
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        let (status, timed_out, out, err) = thread::scope(|scope| {
+            if let (Some(mut pipe), Input::Bytes(bytes)) = (stdin, &self.stdin) {
+                // A child that stops reading early closes the pipe, which is
+                // not an error
+                scope.spawn(move || { let _ = pipe.write_all(bytes); });
+            }
+            let out = scope.spawn(move || collect(stdout, io, false));
+            let err = scope.spawn(move || collect(stderr, io, true));
+            let (status, timed_out) = self.wait(&mut child);
+            (status, timed_out, out.join().unwrap_or_default(), err.join().unwrap_or_default())
+        });
+        let status = status.map_err(|e| ProcessError::Spawn(self.command_line(), e))?;
+        if timed_out {
+            return Err(ProcessError::TimedOut(self.command_line(), self.timeout.unwrap()));
+        }
+        Ok(Completed { status, stdout: out, stderr: err })
+    }
+
+    // Waits for the child, stopping it if it outlives the timeout. Returns its
+    // status and whether it was stopped.
+    fn wait(&self, child: &mut Child) -> (io::Result<process::ExitStatus>, bool) {
+        let timeout = match self.timeout {
+            Some(t) => { t },
+            None => { return (child.wait(), false); },
+        };
+        if let Some(status) = wait_until(child, Instant::now() + timeout) {
+            return (status, false);
+        }
+        stop(child, false);
+        if let Some(status) = wait_until(child, Instant::now() + self.kill_after) {
+            return (status, true);
+        }
+        stop(child, true);
+        (child.wait(), true)
+    }
+}
+
+// Waits for the child to exit until `deadline`, `None` if it is still running
+fn wait_until(child: &mut Child, deadline: Instant) -> Option<io::Result<process::ExitStatus>> {
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => { return Some(Ok(status)); },
+            Ok(None) if Instant::now() < deadline => { thread::sleep(POLL); },
+            Ok(None) => { return None; },
+            Err(e) => { return Some(Err(e)); },
+        }
+    }
+}
+
+// Starts children with a timeout in a process group of their own, so that
+// stopping them also stops anything they started
+#[cfg(unix)]
+fn own_group(cmd: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    cmd.process_group(0);
+}
+
+#[cfg(not(unix))]
+fn own_group(_cmd: &mut Command) {}
+
+// Asks the child's process group to exit, or kills it when `force` is set
+#[cfg(unix)]
+fn stop(child: &mut Child, force: bool) {
+    let sig = if force { libc::SIGKILL } else { libc::SIGTERM };
+    unsafe { libc::kill(-(child.id() as libc::pid_t), sig); }
+}
+
+#[cfg(not(unix))]
+fn stop(child: &mut Child, _force: bool) {
+    let _ = child.kill();
+}
+
+// Reads a child's output pipe, passing each line to `io` as it is, without
+// decoding, when streaming and returning everything read otherwise
+pub(crate) fn collect<R: Read>(pipe: Option<R>, io: Option<&Output>, err: bool) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let pipe = match pipe {
+        Some(p) => { p },
+        None => { return buf; },
+    };
+    let io = match io {
+        Some(io) => { io },
+        None => {
+            let mut pipe = pipe;
+            let _ = pipe.read_to_end(&mut buf);
+            return buf;
+        },
+    };
+    let mut reader = BufReader::new(pipe);
+    while let Ok(n) = reader.read_until(b'\n', &mut buf) {
+        if n == 0 { break; }
+        if err { io.eprint_bytes(&buf); } else { io.print_bytes(&buf); }
+        buf.clear();
+    }
+    Vec::new()
+}
+
+impl Prog {
+    /// Runs `process` with its output printed through `io`, exiting with
+    /// `Exit::error` if it cannot be run, times out or fails. The exit status
+    /// is `ExitStatus::Error`, or the child's if a signal ended it.
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::{ExitStatus,ExitStrategy,Output,Prog};
+    /// use rpf::process::Process;
+    ///
+    /// let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    /// prog.on_exit.set_strategy(ExitStrategy::Return);
+    /// assert!(prog.run(&Process::new("echo").arg("hi")).is_some());
+    /// assert_eq!(prog.io.take_out(), "hi\n");
+    /// assert!(prog.run(&Process::new("false")).is_none());
+    /// assert!(prog.io.take_err().contains("`false` failed with exit status 1"));
+    /// assert_eq!(prog.on_exit.status(), Some(ExitStatus::Error));
+    /// ```
+    pub fn run(&self, process: &Process) -> Option<Completed> {
+        self.finish_process(process.stream(&self.io), process)
+    }
+
+    /// Runs `process` capturing its output, exiting with `Exit::error` if it
+    /// cannot be run, times out or fails. The error includes what the child
+    /// printed on standard error.
+    pub fn run_output(&self, process: &Process) -> Option<Completed> {
+        self.finish_process(process.output(), process)
+    }
+
+    // Reports a child that could not be run or failed, with what it printed on
+    // standard error when that was captured
+    fn finish_process(&self, res: Result<Completed, ProcessError>, process: &Process)
+                      -> Option<Completed> {
+        let (e, stderr) = match res {
+            Ok(ref done) if done.success() => { return res.ok(); },
+            Ok(done) => {
+                (ProcessError::Failed(process.command_line(), done.exit_status()),
+                 done.stderr_str())
+            },
+            Err(e) => { (e, String::new()) },
+        };
+        match stderr.trim_end() {
+            "" => { self.error(e.to_string(), e.status()); },
+            s => { self.error(format!("{}:\n{}", e, s), e.status()); },
+        }
+        None
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn test_process_env_stdin() {
+    let cat = Process::new("sh").args(&["-c", "printf %s \"$GREETING\"; cat; echo oops >&2"])
+        .env("GREETING", "hi ").stdin(Input::Bytes(b"there".to_vec())).current_dir("src");
+    let done = cat.output().unwrap();
+    assert_eq!(done.stdout_str(), "hi there");
+    assert_eq!(done.stderr_str(), "oops\n");
+    let err = Process::new("rpf-no-such-program").arg("a b").output().unwrap_err();
+    assert!(err.to_string().starts_with("Cannot run `rpf-no-such-program 'a b'`: "));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_process_kill_escalation() {
+    let start = Instant::now();
+    let stubborn = Process::new("sh").args(&["-c", "trap '' TERM; sleep 5"])
+        .timeout(Duration::from_millis(50)).kill_after(Duration::from_millis(50));
+    match stubborn.output() {
+        Err(ProcessError::TimedOut(_, t)) => { assert_eq!(t, Duration::from_millis(50)); },
+        other => { panic!("expected a timeout, got {:?}", other); },
+    }
+    assert!(start.elapsed() < Duration::from_secs(4));
+    let cat = Process::new("cat").timeout(Duration::from_secs(5)).output().unwrap();
+    assert!(cat.success());
+    let killed = Process::new("sh").args(&["-c", "kill -9 $$"]).output().unwrap();
+    assert_eq!(killed.exit_status(), ExitStatus::Signal(9));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_process_stream_bytes() {
+    use utils::Stream;
+
+    let io = Output::capture();
+    let bytes = Process::new("printf").arg(r"\377\376\n\200");
+    assert!(bytes.stream(&io).unwrap().success());
+    match io.set_out(Stream::Stdout) {
+        Stream::Buffer(v) => { assert_eq!(v, b"\xff\xfe\n\x80"); },
+        _ => { unreachable!(); },
+    }
+}
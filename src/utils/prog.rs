@@ -2,17 +2,27 @@
 // All rights reserved. This file is part of rpf, distributed under the
 // BSD 3-Clause license. For full terms please see the LICENSE file.
 
-use utils::ExitStatus;
-use std::process;
+use utils::{Exit,ExitStatus,OnExit,Output};
+use utils::build::Build;
+use utils::format::CurrentFormat;
+use utils::license;
+use utils::log::Logger;
+use utils::progress::Bars;
+use utils::signal::BROKEN_PIPE;
+
+use std::io;
 
 /// A structure for storing program information
 ///
+/// Metadata is best filled in from `Cargo.toml` with the `prog!` macro so that
+/// it never drifts from the package.
+///
 /// # Example
 ///
 /// ```
 /// use rpf::Prog;
 ///
-/// static UTIL: Prog = Prog { name: "util", vers: "0.1.0", yr: "2015" };
+/// static UTIL: Prog = Prog::new("util", "0.1.0", "2015");
 /// assert_eq!(UTIL.name, "util");
 /// assert_eq!(UTIL.vers, "0.1.0");
 /// assert_eq!(UTIL.yr, "2015");
@@ -24,17 +34,162 @@ pub struct Prog {
     pub vers: &'static str,
     /// Year of copyright for program
     pub yr: &'static str,
+    /// Authors of program separated by `:`, as in `CARGO_PKG_AUTHORS`
+    pub authors: &'static str,
+    /// SPDX identifier of the program's license
+    pub license: &'static str,
+    /// One line description of program
+    pub description: &'static str,
+    /// Home page of program
+    pub homepage: &'static str,
+    /// Where to report bugs, a URL or an email address
+    pub bugs: &'static str,
+    /// How the program was built
+    pub build: Build,
+    /// Streams that everything the program prints is written to
+    pub io: Output,
+    /// Exit strategy and hooks run by `exit`
+    pub on_exit: OnExit,
+    /// Progress bars currently shown
+    pub bars: Bars,
+    /// Format records and errors are printed in
+    pub format: CurrentFormat,
+    /// Log level, filters and log file
+    pub logger: Logger,
 }
 
 impl Prog {
-    /// Prints copyright, version, and author information
+    /// Creates a `Prog` printing to the process's standard streams
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rpf::{Prog,Output};
+    ///
+    /// let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    /// prog.copyright("", &["Author"]);
+    /// assert_eq!(prog.io.take_out(),
+    ///            "util 0.1.0\nCopyright (C) 2015 util developers\nWritten by Author\n");
+    /// ```
+    pub const fn new(name: &'static str, vers: &'static str, yr: &'static str) -> Prog {
+        Prog {
+            name,
+            vers,
+            yr,
+            authors: "",
+            license: "",
+            description: "",
+            homepage: "",
+            bugs: "",
+            build: Build::empty(),
+            io: Output::std(),
+            on_exit: OnExit::new(),
+            bars: Bars::new(),
+            format: CurrentFormat::new(),
+            logger: Logger::new(),
+        }
+    }
+
+    // Sets the metadata the `prog!` macro reads from `Cargo.toml` and the
+    // build script, keeping the other fields as `new` sets them
+    #[doc(hidden)]
+    pub const fn with_package(mut self, authors: &'static str, license: &'static str,
+                              description: &'static str, homepage: &'static str,
+                              bugs: &'static str, build: Build) -> Prog {
+        self.authors = authors;
+        self.license = license;
+        self.description = description;
+        self.homepage = homepage;
+        self.bugs = bugs;
+        self.build = build;
+        self
+    }
+
+    /// Returns the program's authors
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rpf::Prog;
+    ///
+    /// let prog = Prog { authors: "A <a@example.com>:B", ..Prog::new("util", "0.1.0", "2015") };
+    /// assert_eq!(prog.authors(), vec!["A <a@example.com>", "B"]);
+    /// ```
+    pub fn authors(&self) -> Vec<&'static str> {
+        self.authors.split(':').map(|a| a.trim()).filter(|a| !a.is_empty()).collect()
+    }
+
+    /// Returns the version followed by any build information
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rpf::Prog;
+    /// use rpf::build::Build;
+    ///
+    /// let prog = Prog {
+    ///     build: Build { commit: "1a2b3c4", date: "2015-08-01",
+    ///                    target: "x86_64-unknown-linux-gnu", features: "color" },
+    ///     ..Prog::new("util", "0.1.0", "2015")
+    /// };
+    /// assert_eq!(prog.long_version(),
+    ///            "0.1.0 (1a2b3c4 2015-08-01)\n\
+    ///             target: x86_64-unknown-linux-gnu\n\
+    ///             features: color\n");
+    /// ```
+    pub fn long_version(&self) -> String {
+        let info: Vec<&str> = vec![self.build.commit, self.build.date].into_iter()
+            .filter(|s| !s.is_empty()).collect();
+        let mut vers = self.vers.to_string();
+        if !info.is_empty() {
+            vers.push_str(&format!(" ({})", info.join(" ")));
+        }
+        vers.push('\n');
+        if !self.build.target.is_empty() {
+            vers.push_str(&format!("target: {}\n", self.build.target));
+        }
+        if !self.build.features.is_empty() {
+            vers.push_str(&format!("features: {}\n", self.build.features.replace(',', ", ")));
+        }
+        vers
+    }
+
+    /// Prints the program name and long version, used for `--version`
+    pub fn version(&self) {
+        self.io.print(format_args!("{} {}", self.name, self.long_version()));
+    }
+
+    /// Returns the program name and version followed by its description, for
+    /// the top of help output
+    pub fn banner(&self) -> String {
+        if self.description.is_empty() {
+            format!("{} {}\n", self.name, self.vers)
+        } else {
+            format!("{} {}\n{}\n", self.name, self.vers, self.description)
+        }
+    }
+
+    /// Prints where to report bugs and the program's home page, for the bottom
+    /// of help output
+    pub fn bug_report(&self) {
+        if !self.bugs.is_empty() {
+            self.io.print(format_args!("Report bugs to: {}\n", self.bugs));
+        }
+        if !self.homepage.is_empty() {
+            self.io.print(format_args!("{} home page: <{}>\n", self.name, self.homepage));
+        }
+    }
+
+    /// Prints copyright, version, and author information. When `license` is
+    /// empty the program's `copyright_notice` is printed instead, and the
+    /// program's authors are used when `auth` is empty.
     ///
     /// # Example
     ///
     /// ```
     /// use rpf::Prog;
     ///
-    /// let prog = Prog { name: "util", vers: "0.1.0", yr: "2015" };
+    /// let prog = Prog::new("util", "0.1.0", "2015");
     /// prog.copyright(
     /// "Copyright (C) 2015 util developers\n\
     /// License GPLv3+: GNU GPL version 3 or later <http://gnu.org/licenses/gpl.html>.\n\
@@ -50,27 +205,35 @@ impl Prog {
     /// License GPLv3+: GNU GPL version 3 or later <http://gnu.org/licenses/gpl.html>.
     /// This is free software: you are free to change and redistribute it.
     /// There is NO WARRANTY, to the extent permitted by law.
-    ///
     /// Written by Author
     /// ```
+    ///
+    /// With several authors they are listed as "Written by A, B, and C".
     pub fn copyright(&self, license: &str, auth: &[&str]) {
-        print!("{} {}\n{}", &self.name, &self.vers, license);
-        print!("Written by ");
-        for pers in auth.iter() {
-            print!("{} ", pers);
+        if license.is_empty() {
+            self.io.print(format_args!("{} {}\n{}", &self.name, &self.vers,
+                                       self.copyright_notice()));
+        } else {
+            self.io.print(format_args!("{} {}\n{}", &self.name, &self.vers, license));
+        }
+        let authors = self.authors();
+        let auth = if auth.is_empty() { &authors[..] } else { auth };
+        if !auth.is_empty() {
+            self.io.print(format_args!("Written by {}\n", license::join_names(auth)));
         }
-        print!("\n");
     }
 
     /// Used for when no arguments are given to a util
     ///
     /// # Example
     ///
-    /// ```should panic
-    /// use rpf::Prog;
+    /// ```
+    /// use rpf::{Prog,ExitStatus,ExitStrategy};
     ///
-    /// let prog = Prog { name: "util", vers: "0.1.0", yr: "2015" };
+    /// let prog = Prog::new("util", "0.1.0", "2015");
+    /// prog.on_exit.set_strategy(ExitStrategy::Return);
     /// prog.prog_try();
+    /// assert_eq!(prog.on_exit.status(), Some(ExitStatus::ArgError));
     /// ```
     ///
     /// Would print the following:
@@ -80,31 +243,101 @@ impl Prog {
     /// Try util --help for more information
     /// ```
     pub fn prog_try(&self) {
-        println!("{}: Missing arguments\n\
-             Try '{} --help' for more information", &self.name, &self.name);
-        &self.exit(ExitStatus::ArgError);
+        self.io.eprint(format_args!("{}: Missing arguments\n\
+             Try '{} --help' for more information\n", &self.name, &self.name));
+        self.exit(ExitStatus::ArgError);
     }
 
-    /// Wrapper for `process::exit`, immediately exits the process with the set
-    /// exit status.
+    /// Runs exit hooks, flushes output and then exits with the set exit status
+    /// according to the exit strategy. With the default strategy this is a
+    /// wrapper for `process::exit` and immediately exits the process.
+    ///
+    /// If writing the output stream failed, a successful exit becomes a
+    /// failure: a closed pipe exits quietly as if killed by `SIGPIPE` and any
+    /// other error is reported with `Exit::error`.
     ///
     /// # Example
-    // Ignored here as doc-tests are ended because of `std::process::exit`
-    /// ```ignore
-    /// use rpf::{Prog,Exit,ExitStatus};
+    /// ```
+    /// use rpf::{Prog,ExitStatus,ExitStrategy};
     ///
-    /// let prog = Prog { name: "test", vers: "0.1.0", yr: "2015" };
+    /// let prog = Prog::new("test", "0.1.0", "2015");
+    /// prog.on_exit.set_strategy(ExitStrategy::Return);
     /// prog.exit(ExitStatus::Ok);
+    /// assert_eq!(prog.on_exit.status(), Some(ExitStatus::Ok));
     /// ```
     pub fn exit(&self, status: ExitStatus) {
-        process::exit(status as i32);
+        self.on_exit.run_hooks();
+        self.io.flush();
+        match self.io.take_out_error() {
+            Some(ref e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                self.on_exit.finish(if status == ExitStatus::Ok { BROKEN_PIPE } else { status });
+            },
+            Some(e) => { self.error(format!("write error: {}", e), ExitStatus::Error); },
+            None => { self.on_exit.finish(status); },
+        }
+    }
+
+    /// Exits as `exit` does if writing the output stream failed, for long
+    /// running loops that print
+    pub fn check_output(&self) {
+        if self.io.out_failed() {
+            self.exit(ExitStatus::Ok);
+        }
     }
 
 }
 
+/// Creates a `Prog` from the calling package's `Cargo.toml`, along with build
+/// information set by `rpf::build::emit` in a build script. The copyright year
+/// defaults to the year of the build and may be given as an argument.
+///
+/// `homepage` falls back to the package repository, and `bugs` is read from
+/// the `RPF_BUGS` environment variable at compile time.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use] extern crate rpf;
+///
+/// static UTIL: rpf::Prog = prog!("2015");
+///
+/// fn main() {
+///     assert_eq!(UTIL.name, env!("CARGO_PKG_NAME"));
+///     assert_eq!(UTIL.vers, env!("CARGO_PKG_VERSION"));
+///     assert_eq!(UTIL.yr, "2015");
+/// }
+/// ```
+#[macro_export]
+macro_rules! prog {
+    (@opt $var:expr) => {
+        match option_env!($var) { Some(s) => { s }, None => { "" } }
+    };
+    () => {
+        $crate::prog!($crate::prog!(@opt "RPF_BUILD_YEAR"))
+    };
+    ($yr:expr) => {
+        $crate::Prog::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), $yr).with_package(
+            env!("CARGO_PKG_AUTHORS"),
+            $crate::prog!(@opt "CARGO_PKG_LICENSE"),
+            $crate::prog!(@opt "CARGO_PKG_DESCRIPTION"),
+            match option_env!("CARGO_PKG_HOMEPAGE") {
+                Some(h) if !h.is_empty() => { h },
+                _ => { $crate::prog!(@opt "CARGO_PKG_REPOSITORY") },
+            },
+            $crate::prog!(@opt "RPF_BUGS"),
+            $crate::build::Build {
+                commit: $crate::prog!(@opt "RPF_BUILD_COMMIT"),
+                date: $crate::prog!(@opt "RPF_BUILD_DATE"),
+                target: $crate::prog!(@opt "RPF_BUILD_TARGET"),
+                features: $crate::prog!(@opt "RPF_BUILD_FEATURES"),
+            },
+        )
+    };
+}
+
 #[test]
 fn test_prog() {
-    let prog = Prog { name: "util", vers: "0.1.0", yr: "2015" };
+    let prog = Prog::new("util", "0.1.0", "2015");
     assert_eq!(prog.name, "util");
     assert_eq!(prog.vers, "0.1.0");
     assert_eq!(prog.yr, "2015");
@@ -112,10 +345,35 @@ fn test_prog() {
 
 #[test]
 fn test_prog_copyright() {
-    let prog = Prog { name: "util", vers: "0.1.0", yr: "2015" };
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
     prog.copyright(
     "Copyright (C) 2015 util developers\n\
     License GPLv3+: GNU GPL version 3 or later <http://gnu.org/licenses/gpl.html>.\n\
     This is free software: you are free to change and redistribute it.\n\
     There is NO WARRANTY, to the extent permitted by law.\n", &["Author"]);
+    assert_eq!(prog.io.take_out(),
+    "util 0.1.0\n\
+    Copyright (C) 2015 util developers\n\
+    License GPLv3+: GNU GPL version 3 or later <http://gnu.org/licenses/gpl.html>.\n\
+    This is free software: you are free to change and redistribute it.\n\
+    There is NO WARRANTY, to the extent permitted by law.\n\
+    Written by Author\n");
+}
+
+#[test]
+fn test_prog_macro() {
+    let prog = prog!();
+    assert_eq!(prog.name, "rpf");
+    assert_eq!(prog.license, "BSD-3-Clause");
+    assert_eq!(prog.homepage, "https://gitlab.com/0X1A/rpf");
+    assert_eq!(prog.authors(), vec!["Alberto Corona <ac@albertocorona.com>"]);
+    assert!(prog.long_version().starts_with(env!("CARGO_PKG_VERSION")));
+}
+
+#[test]
+fn test_prog_copyright_authors() {
+    let prog = Prog { io: Output::capture(), authors: "A:B",
+                      ..Prog::new("util", "0.1.0", "2015") };
+    prog.copyright("License: none\n", &[]);
+    assert_eq!(prog.io.take_out(), "util 0.1.0\nLicense: none\nWritten by A and B\n");
 }
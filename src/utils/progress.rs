@@ -0,0 +1,363 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Progress bars and spinners
+//!
+//! Bars are drawn on the program's error stream. When it is a terminal every
+//! bar is redrawn in place below any finished ones, otherwise a plain line is
+//! printed for each bar at most once per `Bars::set_interval` and when it
+//! finishes. `Bar` handles can be moved to other threads when the `Prog` is a
+//! `static`, or used from scoped threads.
+//!
+//! ```
+//! use rpf::{Output,Prog};
+//!
+//! let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+//! let bar = prog.progress("Copying", 3);
+//! for _ in 0..3 {
+//!     bar.inc(1);
+//! }
+//! bar.finish();
+//! assert_eq!(prog.io.take_err(), "Copying: 3/3 (100%) done\n");
+//! ```
+
+use utils::Prog;
+use utils::output::Output;
+use utils::term;
+use utils::text;
+
+use std::sync::{Mutex,MutexGuard};
+use std::time::{Duration,Instant};
+
+const WIDTH: usize = 30;
+const SPINNER: &[char] = &['|', '/', '-', '\\'];
+const REDRAW: Duration = Duration::from_millis(100);
+
+#[derive(Copy,Clone,Debug,PartialEq)]
+enum Kind {
+    Count,
+    Bytes,
+    Spinner,
+}
+
+struct BarState {
+    id: usize,
+    kind: Kind,
+    mesg: String,
+    pos: u64,
+    total: u64,
+    start: Instant,
+    last_plain: Option<Instant>,
+}
+
+struct State {
+    bars: Vec<BarState>,
+    next: usize,
+    drawn: usize,
+    last_draw: Option<Instant>,
+    interval: Duration,
+    tick: usize,
+}
+
+/// The progress bars of a `Prog`
+pub struct Bars {
+    state: Mutex<State>,
+}
+
+/// Handle to a progress bar or spinner, finished when dropped
+pub struct Bar<'a> {
+    prog: &'a Prog,
+    id: usize,
+}
+
+impl Bars {
+    /// Creates an empty set of bars
+    pub const fn new() -> Bars {
+        Bars {
+            state: Mutex::new(State { bars: Vec::new(), next: 0, drawn: 0, last_draw: None,
+                                      interval: Duration::from_secs(1), tick: 0 }),
+        }
+    }
+
+    /// Sets how often plain lines are printed when not writing to a terminal
+    pub fn set_interval(&self, interval: Duration) {
+        lock(&self.state).interval = interval;
+    }
+
+    /// Erases bars drawn on the terminal so a message can be printed, they
+    /// are drawn again on their next update
+    pub fn clear(&self, io: &Output) {
+        let mut state = lock(&self.state);
+        if state.drawn > 0 {
+            io.eprint(format_args!("\x1b[{}A\x1b[J", state.drawn));
+            io.flush();
+            state.drawn = 0;
+        }
+    }
+
+    fn add(&self, kind: Kind, mesg: &str, total: u64) -> usize {
+        let mut state = lock(&self.state);
+        let id = state.next;
+        state.next += 1;
+        state.bars.push(BarState { id, kind, mesg: mesg.to_string(), pos: 0, total,
+                                   start: Instant::now(), last_plain: None });
+        id
+    }
+
+    fn update<F: FnOnce(&mut BarState)>(&self, io: &Output, id: usize, f: F) {
+        let mut state = lock(&self.state);
+        match state.bars.iter_mut().find(|b| b.id == id) {
+            Some(bar) => { f(bar); },
+            None => { return; },
+        }
+        if io.err_is_tty() {
+            let now = Instant::now();
+            if state.last_draw.is_some_and(|t| now.duration_since(t) < REDRAW) { return; }
+            state.last_draw = Some(now);
+            state.tick += 1;
+            draw(&mut state, io, None);
+        } else {
+            let interval = state.interval;
+            let bar = match state.bars.iter_mut().find(|b| b.id == id) {
+                Some(b) => { b },
+                None => { return; },
+            };
+            let due = match bar.last_plain {
+                Some(t) => { t.elapsed() >= interval },
+                None => { bar.start.elapsed() >= interval },
+            };
+            if due {
+                bar.last_plain = Some(Instant::now());
+                io.eprint(format_args!("{}\n", plain(bar)));
+            }
+        }
+    }
+
+    // Removes a bar, printing its final state. Bars dropped without being
+    // completed keep their position.
+    fn finish(&self, io: &Output, id: usize, complete: bool, mesg: Option<&str>) {
+        let mut state = lock(&self.state);
+        let i = match state.bars.iter().position(|b| b.id == id) {
+            Some(i) => { i },
+            None => { return; },
+        };
+        let mut bar = state.bars.remove(i);
+        if complete && bar.kind != Kind::Spinner { bar.pos = bar.total; }
+        let line = match mesg {
+            Some(m) => { format!("{}: {}", bar.mesg, m) },
+            None if complete => { format!("{} done", plain(&bar)) },
+            None => { plain(&bar) },
+        };
+        if io.err_is_tty() {
+            draw(&mut state, io, Some(&line));
+        } else {
+            io.eprint(format_args!("{}\n", line));
+        }
+    }
+}
+
+impl Default for Bars {
+    fn default() -> Bars {
+        Bars::new()
+    }
+}
+
+impl Prog {
+    /// Starts a progress bar counting to `total`
+    pub fn progress(&self, mesg: &str, total: u64) -> Bar<'_> {
+        Bar { prog: self, id: self.bars.add(Kind::Count, mesg, total) }
+    }
+
+    /// Starts a progress bar counting `total` bytes, showing the rate and the
+    /// time remaining
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::{Output,Prog};
+    ///
+    /// let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    /// let bar = prog.progress_bytes("Downloading", 3 << 20);
+    /// bar.finish();
+    /// assert!(prog.io.take_err().starts_with("Downloading: 3.0 MiB/3.0 MiB (100%)"));
+    /// ```
+    pub fn progress_bytes(&self, mesg: &str, total: u64) -> Bar<'_> {
+        Bar { prog: self, id: self.bars.add(Kind::Bytes, mesg, total) }
+    }
+
+    /// Starts a spinner for work of unknown length, counting with `inc`
+    pub fn spinner(&self, mesg: &str) -> Bar<'_> {
+        Bar { prog: self, id: self.bars.add(Kind::Spinner, mesg, 0) }
+    }
+}
+
+impl<'a> Bar<'a> {
+    /// Advances the bar by `n`
+    pub fn inc(&self, n: u64) {
+        self.prog.bars.update(&self.prog.io, self.id, |b| b.pos = b.pos.saturating_add(n));
+    }
+
+    /// Sets the bar's position
+    pub fn set(&self, pos: u64) {
+        self.prog.bars.update(&self.prog.io, self.id, |b| b.pos = pos);
+    }
+
+    /// Redraws the bar without advancing it, turning a spinner
+    pub fn tick(&self) {
+        self.prog.bars.update(&self.prog.io, self.id, |_| {});
+    }
+
+    /// Changes the bar's message
+    pub fn set_message(&self, mesg: &str) {
+        self.prog.bars.update(&self.prog.io, self.id, |b| b.mesg = mesg.to_string());
+    }
+
+    /// Completes the bar, leaving its final state printed
+    pub fn finish(self) {
+        self.prog.bars.finish(&self.prog.io, self.id, true, None);
+    }
+
+    /// Completes the bar, printing `mesg` in place of its final state
+    pub fn finish_with(self, mesg: &str) {
+        self.prog.bars.finish(&self.prog.io, self.id, true, Some(mesg));
+    }
+}
+
+impl<'a> Drop for Bar<'a> {
+    fn drop(&mut self) {
+        self.prog.bars.finish(&self.prog.io, self.id, false, None);
+    }
+}
+
+// Redraws the bars in place, printing `done` above them first
+fn draw(state: &mut State, io: &Output, done: Option<&str>) {
+    let mut out = String::new();
+    if state.drawn > 0 {
+        out.push_str(&format!("\x1b[{}A", state.drawn));
+    }
+    if let Some(line) = done {
+        out.push_str(&format!("\r\x1b[2K{}\n", line));
+    }
+    let cols = term::width().saturating_sub(1);
+    for bar in state.bars.iter() {
+        out.push_str(&format!("\r\x1b[2K{}\n", text::truncate(&fancy(bar, state.tick), cols)));
+    }
+    out.push_str("\x1b[J");
+    state.drawn = state.bars.len();
+    io.eprint(format_args!("{}", out));
+    io.flush();
+}
+
+// The line drawn for a bar on a terminal
+fn fancy(bar: &BarState, tick: usize) -> String {
+    if bar.kind == Kind::Spinner {
+        return format!("{} {} {}", SPINNER[tick % SPINNER.len()], bar.mesg, bar.pos);
+    }
+    let filled = share(bar, WIDTH as u64) as usize;
+    let mut line = format!("{} [{}{}] {}", bar.mesg, "#".repeat(filled),
+                           "-".repeat(WIDTH - filled), amount(bar));
+    if let Some(eta) = eta(bar) {
+        line.push_str(&format!(" ETA {}", eta));
+    }
+    line
+}
+
+// The line printed for a bar when not writing to a terminal
+fn plain(bar: &BarState) -> String {
+    match bar.kind {
+        Kind::Spinner => { format!("{}: {}", bar.mesg, bar.pos) },
+        _ => { format!("{}: {}", bar.mesg, amount(bar)) },
+    }
+}
+
+// The part of `of` done, computed wide so that large totals cannot overflow
+fn share(bar: &BarState, of: u64) -> u64 {
+    (of as u128 * bar.pos.min(bar.total) as u128 / bar.total.max(1) as u128) as u64
+}
+
+// Position, total, percentage and for bytes the rate
+fn amount(bar: &BarState) -> String {
+    let pct = if bar.total == 0 { 100 } else { share(bar, 100) };
+    match bar.kind {
+        Kind::Bytes => {
+            let secs = bar.start.elapsed().as_secs_f64();
+            let mut text = format!("{}/{} ({}%)", bytes(bar.pos), bytes(bar.total), pct);
+            if secs >= 1.0 && bar.pos < bar.total {
+                text.push_str(&format!(" {}/s", bytes((bar.pos as f64 / secs) as u64)));
+            }
+            text
+        },
+        _ => { format!("{}/{} ({}%)", bar.pos, bar.total, pct) },
+    }
+}
+
+fn eta(bar: &BarState) -> Option<String> {
+    if bar.pos == 0 || bar.pos >= bar.total { return None; }
+    let secs = bar.start.elapsed().as_secs_f64() * (bar.total - bar.pos) as f64 / bar.pos as f64;
+    let secs = secs as u64;
+    Some(format!("{}:{:02}", secs / 60, secs % 60))
+}
+
+// Formats a size in bytes with IEC units
+fn bytes(n: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    if n < 1024 { return format!("{} B", n); }
+    let mut size = n as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+// Bars remain usable after a panic while drawing
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    match state.lock() {
+        Ok(guard) => { guard },
+        Err(poisoned) => { poisoned.into_inner() },
+    }
+}
+
+#[test]
+fn test_progress_plain_interval() {
+    let prog = Prog { io: ::utils::Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    prog.bars.set_interval(Duration::from_secs(0));
+    let spin = prog.spinner("Scanning");
+    spin.inc(2);
+    spin.finish_with("found 2");
+    assert_eq!(prog.io.take_err(), "Scanning: 2\nScanning: found 2\n");
+}
+
+#[test]
+fn test_progress_threads() {
+    use std::thread;
+
+    let prog = Prog { io: ::utils::Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    thread::scope(|s| {
+        for i in 0..4 {
+            let bar = prog.progress(&format!("worker {}", i), 10);
+            s.spawn(move || {
+                for _ in 0..10 { bar.inc(1); }
+                bar.finish();
+            });
+        }
+    });
+    let err = prog.io.take_err();
+    assert_eq!(err.lines().count(), 4);
+    assert!(err.contains("worker 3: 10/10 (100%) done\n"));
+    drop(prog.progress("Failing", 10));
+    assert_eq!(prog.io.take_err(), "Failing: 0/10 (0%)\n");
+}
+
+#[test]
+fn test_progress_format() {
+    assert_eq!(bytes(512), "512 B");
+    assert_eq!(bytes(1536), "1.5 KiB");
+    let bar = BarState { id: 0, kind: Kind::Count, mesg: "Copying".to_string(), pos: 5,
+                         total: 10, start: Instant::now(), last_plain: None };
+    assert!(fancy(&bar, 0).starts_with("Copying [###############---------------] 5/10 (50%)"));
+    let huge = BarState { pos: u64::MAX / 2, total: u64::MAX, ..bar };
+    assert!(fancy(&huge, 0).contains("[##############----------------]"));
+    assert!(amount(&huge).ends_with("(49%)"));
+}
@@ -0,0 +1,349 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Interactive prompts
+//!
+//! Questions are written to the program's error stream so they do not mix
+//! with output that may be piped elsewhere, and answers are read from standard
+//! input. Prompts refuse with `PromptError::NotTty` when standard input is not
+//! a terminal, except for `confirm` when `--yes` was given.
+//!
+//! ```no_run
+//! use rpf::{Cmd,Prog,prompt};
+//!
+//! let prog = Prog::new("util", "0.1.0", "2015");
+//! let m = prog.parse_args(&Cmd::new("util").opt(prompt::YES));
+//! let prompt = prog.prompt().assume_yes(m.has("yes"));
+//! if prompt.confirm("Delete everything?", false).unwrap_or(false) {
+//!     println!("deleting");
+//! }
+//! ```
+
+use utils::{Color,Opt,Prog,Styled};
+use utils::term::{self,Mode,RawMode};
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self,Read};
+
+/// Option that makes `Prompt::confirm` answer yes without asking
+pub static YES: Opt = Opt::flag("yes", "Answer yes to all questions").short('y');
+
+/// Reasons a prompt could not be answered
+#[derive(Debug)]
+pub enum PromptError {
+    /// Standard input is not a terminal
+    NotTty,
+    /// Input ended or the user pressed Ctrl-C
+    Interrupted,
+    /// There were no items to pick from
+    NoItems,
+    /// Reading from or configuring the terminal failed
+    Io(io::Error),
+}
+
+impl fmt::Display for PromptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PromptError::NotTty => {
+                write!(f, "Cannot prompt for input: standard input is not a terminal")
+            },
+            PromptError::Interrupted => { write!(f, "Interrupted") },
+            PromptError::NoItems => { write!(f, "Cannot prompt for a choice: nothing to pick") },
+            PromptError::Io(ref e) => { write!(f, "Cannot read from terminal: {}", e) },
+        }
+    }
+}
+
+impl From<io::Error> for PromptError {
+    fn from(e: io::Error) -> PromptError {
+        PromptError::Io(e)
+    }
+}
+
+#[derive(Copy,Clone,Debug,PartialEq)]
+enum Key {
+    Up,
+    Down,
+    Enter,
+    Space,
+    Char(char),
+    Other,
+}
+
+/// Asks questions on the terminal, created with `Prog::prompt`
+pub struct Prompt<'a> {
+    prog: &'a Prog,
+    yes: bool,
+    tty: bool,
+    raw: bool,
+    input: RefCell<Box<dyn Read + 'a>>,
+}
+
+impl Prog {
+    /// Returns a `Prompt` reading from standard input
+    pub fn prompt(&self) -> Prompt<'_> {
+        let tty = term::is_tty(term::STDIN);
+        Prompt { prog: self, yes: false, tty, raw: tty,
+                 input: RefCell::new(Box::new(io::stdin())) }
+    }
+}
+
+impl<'a> Prompt<'a> {
+    /// Makes `confirm` answer yes without asking when `yes` is true, usually
+    /// `m.has("yes")` for `YES`
+    pub fn assume_yes(mut self, yes: bool) -> Prompt<'a> {
+        self.yes = yes;
+        self
+    }
+
+    /// Reads answers from `input` instead of standard input, as if it were a
+    /// terminal. Arrow keys are read as the escape sequences a terminal sends.
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::{Output,Prog};
+    ///
+    /// let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    /// let prompt = prog.prompt().input_from(&b"\x1b[B\n"[..]);
+    /// assert_eq!(prompt.select("Pick one", &["a", "b", "c"], 0).unwrap(), 1);
+    /// ```
+    pub fn input_from<R: Read + 'a>(mut self, input: R) -> Prompt<'a> {
+        self.tty = true;
+        self.raw = false;
+        self.input = RefCell::new(Box::new(input));
+        self
+    }
+
+    /// Asks a yes or no question, an empty answer gives `default`
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::{Output,Prog};
+    ///
+    /// let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    /// let prompt = prog.prompt().input_from(&b"maybe\ny\n"[..]);
+    /// assert!(prompt.confirm("Continue?", false).unwrap());
+    /// assert!(prog.io.take_err().contains("Please answer yes or no"));
+    /// ```
+    pub fn confirm(&self, question: &str, default: bool) -> Result<bool, PromptError> {
+        if self.yes { return Ok(true); }
+        self.check_tty()?;
+        let hint = if default { "[Y/n]" } else { "[y/N]" };
+        loop {
+            self.ask(&format!("{} {} ", question.bold(), hint));
+            let answer = self.read_line()?;
+            match &answer.trim().to_lowercase()[..] {
+                "" => { return Ok(default); },
+                "y" | "yes" => { return Ok(true); },
+                "n" | "no" => { return Ok(false); },
+                _ => { self.warn("Please answer yes or no"); },
+            }
+        }
+    }
+
+    /// Asks for text, converted with `parser` which is asked again until it
+    /// accepts the answer. An empty answer gives `default` if there is one.
+    /// The parsers in the `value` module can be used.
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::{Output,Prog,value};
+    ///
+    /// let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    /// let prompt = prog.prompt().input_from(&b"0\n8080\n"[..]);
+    /// assert_eq!(prompt.input("Port", None, value::range(1u16, 65535)).unwrap(), 8080);
+    /// assert!(prog.io.take_err().contains("must be between 1 and 65535"));
+    /// ```
+    pub fn input<T, F>(&self, question: &str, default: Option<&str>, parser: F)
+                       -> Result<T, PromptError>
+        where F: Fn(&str) -> Result<T, String> {
+        self.check_tty()?;
+        loop {
+            match default {
+                Some(d) => { self.ask(&format!("{} [{}]: ", question.bold(), d)); },
+                None => { self.ask(&format!("{}: ", question.bold())); },
+            }
+            let line = self.read_line()?;
+            let answer = match default {
+                Some(d) if line.is_empty() => { d },
+                _ => { &line[..] },
+            };
+            match parser(answer) {
+                Ok(v) => { return Ok(v); },
+                Err(e) => { self.warn(&e); },
+            }
+        }
+    }
+
+    /// Asks for a password without showing what is typed
+    pub fn password(&self, question: &str) -> Result<String, PromptError> {
+        self.check_tty()?;
+        self.ask(&format!("{}: ", question.bold()));
+        let _mode = self.mode(Mode::NoEcho)?;
+        let line = self.read_line();
+        self.ask("\n");
+        line
+    }
+
+    /// Asks to pick one of `items` with the arrow keys, or `j` and `k`, and
+    /// Enter, returning its index. `default` is selected at first. Fails with
+    /// `PromptError::NoItems` if `items` is empty.
+    pub fn select(&self, question: &str, items: &[&str], default: usize)
+                  -> Result<usize, PromptError> {
+        check_items(items)?;
+        self.check_tty()?;
+        let _mode = self.mode(Mode::Raw)?;
+        let mut cur = default.min(items.len() - 1);
+        self.ask(&format!("{}\n", question.bold()));
+        loop {
+            let lines: Vec<String> = items.iter().enumerate().map(|(i, item)| {
+                if i == cur { format!("{} {}", ">".paint(Color::Cyan), item.bold()) }
+                else { format!("  {}", item) }
+            }).collect();
+            self.ask(&format!("{}\n", lines.join("\n")));
+            match self.read_key()? {
+                Key::Up | Key::Char('k') => { cur = cur.saturating_sub(1); },
+                Key::Down | Key::Char('j') if cur + 1 < items.len() => { cur += 1; },
+                Key::Enter => { return Ok(cur); },
+                _ => {},
+            }
+            self.ask(&format!("\x1b[{}A\x1b[J", items.len()));
+        }
+    }
+
+    /// Asks to pick any of `items`, moving with the arrow keys and toggling
+    /// with Space until Enter is pressed, returning their indices. `defaults`
+    /// gives the items selected at first. Fails with `PromptError::NoItems` if
+    /// `items` is empty.
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::{Output,Prog};
+    ///
+    /// let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    /// let prompt = prog.prompt().input_from(&b" \x1b[B\x1b[B \r"[..]);
+    /// assert_eq!(prompt.multi_select("Pick", &["a", "b", "c"], &[]).unwrap(), vec![0, 2]);
+    /// ```
+    pub fn multi_select(&self, question: &str, items: &[&str], defaults: &[usize])
+                        -> Result<Vec<usize>, PromptError> {
+        check_items(items)?;
+        self.check_tty()?;
+        let _mode = self.mode(Mode::Raw)?;
+        let mut picked: Vec<bool> = (0..items.len()).map(|i| defaults.contains(&i)).collect();
+        let mut cur = 0;
+        self.ask(&format!("{} (Space to toggle, Enter to accept)\n", question.bold()));
+        loop {
+            let lines: Vec<String> = items.iter().enumerate().map(|(i, item)| {
+                let mark = if picked[i] { "[x]".paint(Color::Green) }
+                           else { "[ ]".paint(Color::White) };
+                if i == cur { format!("{} {} {}", ">".paint(Color::Cyan), mark, item.bold()) }
+                else { format!("  {} {}", mark, item) }
+            }).collect();
+            self.ask(&format!("{}\n", lines.join("\n")));
+            match self.read_key()? {
+                Key::Up | Key::Char('k') => { cur = cur.saturating_sub(1); },
+                Key::Down | Key::Char('j') if cur + 1 < items.len() => { cur += 1; },
+                Key::Space => { picked[cur] = !picked[cur]; },
+                Key::Enter => {
+                    return Ok((0..items.len()).filter(|&i| picked[i]).collect());
+                },
+                _ => {},
+            }
+            self.ask(&format!("\x1b[{}A\x1b[J", items.len()));
+        }
+    }
+
+    fn check_tty(&self) -> Result<(), PromptError> {
+        if self.tty { Ok(()) } else { Err(PromptError::NotTty) }
+    }
+
+    // Changes the terminal mode when reading from the real terminal
+    fn mode(&self, mode: Mode) -> Result<Option<RawMode>, PromptError> {
+        if self.raw { Ok(Some(RawMode::enable(term::STDIN, mode)?)) } else { Ok(None) }
+    }
+
+    fn ask(&self, text: &str) {
+        self.prog.io.eprint(format_args!("{}", text));
+        self.prog.io.flush();
+    }
+
+    fn warn(&self, text: &str) {
+        self.ask(&format!("{}\n", text.paint(Color::Red)));
+    }
+
+    fn read_byte(&self) -> Result<u8, PromptError> {
+        let mut buf = [0u8];
+        // A signal recorded by `signal::install` interrupts the read
+        match self.input.borrow_mut().read(&mut buf) {
+            Ok(0) => { Err(PromptError::Interrupted) },
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+                Err(PromptError::Interrupted)
+            },
+            Err(e) => { Err(PromptError::Io(e)) },
+            _ if buf[0] == 3 => { Err(PromptError::Interrupted) },
+            _ => { Ok(buf[0]) },
+        }
+    }
+
+    fn read_line(&self) -> Result<String, PromptError> {
+        let mut line = Vec::new();
+        loop {
+            match self.read_byte()? {
+                b'\n' => { break; },
+                b'\r' => {},
+                b => { line.push(b); },
+            }
+        }
+        Ok(String::from_utf8_lossy(&line).into_owned())
+    }
+
+    fn read_key(&self) -> Result<Key, PromptError> {
+        match self.read_byte()? {
+            b'\r' | b'\n' => { Ok(Key::Enter) },
+            b' ' => { Ok(Key::Space) },
+            0x1b => {
+                if self.read_byte()? != b'[' { return Ok(Key::Other); }
+                match self.read_byte()? {
+                    b'A' => { Ok(Key::Up) },
+                    b'B' => { Ok(Key::Down) },
+                    _ => { Ok(Key::Other) },
+                }
+            },
+            b => { Ok(Key::Char(b as char)) },
+        }
+    }
+}
+
+// There is nothing to pick from no items, and no index to return
+fn check_items(items: &[&str]) -> Result<(), PromptError> {
+    if items.is_empty() { Err(PromptError::NoItems) } else { Ok(()) }
+}
+
+#[test]
+fn test_prompt_not_tty() {
+    use utils::Output;
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    let prompt = Prompt { prog: &prog, yes: false, tty: false, raw: false,
+                          input: RefCell::new(Box::new(&b"y\n"[..])) };
+    assert!(matches!(prompt.confirm("Sure?", true), Err(PromptError::NotTty)));
+    assert!(prompt.assume_yes(true).confirm("Sure?", false).unwrap());
+    assert_eq!(prog.io.take_err(), "");
+}
+
+#[test]
+fn test_prompt_defaults() {
+    use utils::Output;
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    let prompt = prog.prompt().input_from(&b"\n\n\x03"[..]);
+    assert!(prompt.confirm("Sure?", true).unwrap());
+    assert_eq!(prompt.input("Name", Some("anon"), |s| Ok(s.to_string())).unwrap(), "anon");
+    assert!(matches!(prompt.password("Password"), Err(PromptError::Interrupted)));
+    assert!(prog.io.take_err().contains("[Y/n]"));
+    let empty = prog.prompt().input_from(&b"\r"[..]);
+    assert!(matches!(empty.select("Pick", &[], 0), Err(PromptError::NoItems)));
+    assert!(matches!(empty.multi_select("Pick", &[], &[]), Err(PromptError::NoItems)));
+}
@@ -0,0 +1,186 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Stopping cleanly on `SIGINT`, `SIGTERM` and `SIGHUP`
+//!
+//! After `Prog::handle_signals` these signals no longer end the program at
+//! once, they are recorded instead. `SIGPIPE` stays ignored, writing to a
+//! closed pipe is handled by `Prog::exit` instead. Long operations poll a
+//! `Token` or call `Prog::check_signals`, which runs the exit hooks, such as
+//! those removing partial files registered with `OnExit::remove`, and exits
+//! quietly with `ExitStatus::Signal`. A second signal before then ends the
+//! program at once.
+//!
+//! ```no_run
+//! use rpf::Prog;
+//!
+//! let prog = Prog::new("util", "0.1.0", "2015");
+//! prog.handle_signals();
+//! prog.on_exit.remove("out.partial");
+//! for _ in 0..1000 {
+//!     prog.check_signals();
+//!     // copy another block to out.partial
+//! }
+//! ```
+
+#[cfg(unix)]
+extern crate libc;
+
+use utils::{ExitStatus,Prog};
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool,AtomicI32,Ordering};
+
+/// Status a program exits with after writing to a closed pipe, as if it was
+/// ended by `SIGPIPE`
+#[cfg(unix)]
+pub const BROKEN_PIPE: ExitStatus = ExitStatus::Signal(libc::SIGPIPE);
+
+/// Status a program exits with after writing to a closed pipe
+#[cfg(not(unix))]
+pub const BROKEN_PIPE: ExitStatus = ExitStatus::Error;
+
+// Number of the last signal received, 0 if none
+static PENDING: AtomicI32 = AtomicI32::new(0);
+
+#[cfg(unix)]
+extern "C" fn on_signal(sig: libc::c_int) {
+    PENDING.store(sig, Ordering::SeqCst);
+}
+
+/// Records `SIGINT`, `SIGTERM` and `SIGHUP` instead of ending the program.
+/// Each handler is reset once it has run, so a repeated signal ends the
+/// program. Does nothing on platforms without signals.
+#[cfg(unix)]
+pub fn install() {
+    let handler = on_signal as extern "C" fn(libc::c_int);
+    for &sig in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP].iter() {
+        unsafe {
+            let mut action: libc::sigaction = ::std::mem::zeroed();
+            action.sa_sigaction = handler as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESETHAND;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(sig, &action, ::std::ptr::null_mut());
+        }
+    }
+}
+
+/// Records signals instead of ending the program
+#[cfg(not(unix))]
+pub fn install() {}
+
+/// Returns the number of the signal received, if any
+pub fn pending() -> Option<i32> {
+    match PENDING.load(Ordering::SeqCst) {
+        0 => { None },
+        sig => { Some(sig) },
+    }
+}
+
+/// Cancellation flag for long operations, set by a signal or by `cancel`.
+/// Clones share the flag, so one can be handed to each worker thread.
+///
+/// # Example
+/// ```
+/// use rpf::signal::Token;
+///
+/// let token = Token::new();
+/// let worker = token.clone();
+/// assert!(!worker.is_cancelled());
+/// token.cancel();
+/// assert!(worker.is_cancelled());
+/// ```
+#[derive(Clone,Debug,Default)]
+pub struct Token {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Token {
+    /// Creates a token that is not cancelled
+    pub fn new() -> Token {
+        Token { cancelled: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// Cancels the token and its clones
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true if the token was cancelled or a signal was received
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst) || pending().is_some()
+    }
+}
+
+impl Prog {
+    /// Records signals that would end the program so that it can clean up,
+    /// see `signal::install`
+    pub fn handle_signals(&self) {
+        install();
+    }
+
+    /// Exits with `ExitStatus::Signal` if a signal was received, after
+    /// clearing progress bars and running exit hooks. Nothing is printed. If
+    /// the exit strategy returns or unwinds, the handlers are installed again
+    /// so that the next signal is recorded as well.
+    pub fn check_signals(&self) {
+        // Reinstalls the handlers once the exit hooks have run
+        struct Reinstall;
+        impl Drop for Reinstall {
+            fn drop(&mut self) { install(); }
+        }
+
+        let sig = PENDING.swap(0, Ordering::SeqCst);
+        if sig != 0 {
+            let _reinstall = Reinstall;
+            self.bars.clear(&self.io);
+            self.exit(ExitStatus::Signal(sig));
+        }
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn test_signal_cleanup() {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+    use utils::{ExitStrategy,Output};
+    use utils::process::{Input,Process};
+
+    // Signals go to the whole process, so the test raises them in a copy of
+    // itself rather than among the other tests
+    if env::var_os("RPF_SIGNAL_TEST").is_none() {
+        let out = Command::new(env::current_exe().unwrap())
+            .args(["--exact", "utils::signal::test_signal_cleanup", "--test-threads", "1"])
+            .env("RPF_SIGNAL_TEST", "1").output().unwrap();
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stdout));
+        return;
+    }
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    prog.on_exit.set_strategy(ExitStrategy::Return);
+    prog.handle_signals();
+    fs::write("test-signal.partial", "half").unwrap();
+    prog.on_exit.remove("test-signal.partial");
+    let token = Token::new();
+    unsafe { libc::raise(libc::SIGHUP); }
+    assert_eq!(pending(), Some(libc::SIGHUP));
+    assert!(token.is_cancelled());
+    prog.check_signals();
+    assert_eq!(prog.on_exit.status(), Some(ExitStatus::Signal(libc::SIGHUP)));
+    assert_eq!(ExitStatus::Signal(libc::SIGHUP).code(), 129);
+    assert!(!Path::new("test-signal.partial").exists());
+    assert_eq!(pending(), None);
+    assert_eq!(prog.io.take_err(), "");
+    unsafe { libc::raise(libc::SIGHUP); }
+    assert_eq!(pending(), Some(libc::SIGHUP));
+    prog.check_signals();
+    let input = Input::Bytes(vec![0; 1 << 20]);
+    for _ in 0..3 {
+        assert!(Process::new("true").stdin(input.clone()).output().unwrap().success());
+    }
+    assert_eq!(pending(), None);
+}
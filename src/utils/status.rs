@@ -6,31 +6,203 @@ use utils::PathMod;
 use utils::Styled;
 use utils::Color;
 
+use std::any::Any;
+use std::fs;
+use std::panic::{self,AssertUnwindSafe};
 use std::path::PathBuf;
+use std::process;
+use std::sync::{Mutex,MutexGuard};
 
 use utils::Prog;
 
 /// Enum used for setting exit statuses
-#[derive(Copy,Clone)]
+#[derive(Copy,Clone,Debug,PartialEq)]
 pub enum ExitStatus {
     Ok,
     Error,
     OptError,
     ArgError,
+    /// Ended by the signal with this number, exiting with `128` plus the number
+    Signal(i32),
+}
+
+impl ExitStatus {
+    /// Every exit status except `Signal`, in order of code
+    pub const ALL: [ExitStatus; 4] = [ExitStatus::Ok, ExitStatus::Error,
+                                      ExitStatus::OptError, ExitStatus::ArgError];
+
+    /// Returns the code the process exits with
+    pub fn code(&self) -> i32 {
+        match *self {
+            ExitStatus::Ok => { 0 },
+            ExitStatus::Error => { 1 },
+            ExitStatus::OptError => { 2 },
+            ExitStatus::ArgError => { 3 },
+            ExitStatus::Signal(n) => { 128 + n },
+        }
+    }
+
+    /// Returns a description of when the status is used
+    pub fn description(&self) -> &'static str {
+        match *self {
+            ExitStatus::Ok => { "Success" },
+            ExitStatus::Error => { "General error" },
+            ExitStatus::OptError => { "Invalid options" },
+            ExitStatus::ArgError => { "Missing or invalid arguments" },
+            ExitStatus::Signal(_) => { "Terminated by a signal" },
+        }
+    }
+}
+
+/// What `Prog::exit` does once exit hooks have run
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum ExitStrategy {
+    /// Terminates the process with `std::process::exit`, the default
+    Process,
+    /// Records the exit status and returns to the caller
+    Return,
+    /// Unwinds the stack with the `ExitStatus` as the payload, which can be
+    /// recovered with `catch_exit`
+    Unwind,
+}
+
+type Hook = Box<dyn FnMut() + Send>;
+
+/// The exit strategy and exit hooks of a `Prog`
+///
+/// # Example
+///
+/// ```
+/// use rpf::{Prog,ExitStatus,ExitStrategy};
+///
+/// let prog = Prog::new("util", "0.1.0", "2015");
+/// prog.on_exit.set_strategy(ExitStrategy::Return);
+/// prog.on_exit.hook(|| println!("cleaning up"));
+/// prog.exit(ExitStatus::Error);
+/// assert_eq!(prog.on_exit.status(), Some(ExitStatus::Error));
+/// ```
+pub struct OnExit {
+    strategy: Mutex<ExitStrategy>,
+    hooks: Mutex<Vec<Hook>>,
+    status: Mutex<Option<ExitStatus>>,
+}
+
+impl OnExit {
+    /// Creates an `OnExit` using `ExitStrategy::Process` and no hooks
+    pub const fn new() -> OnExit {
+        OnExit {
+            strategy: Mutex::new(ExitStrategy::Process),
+            hooks: Mutex::new(Vec::new()),
+            status: Mutex::new(None),
+        }
+    }
+
+    /// Returns the current exit strategy
+    pub fn strategy(&self) -> ExitStrategy {
+        *lock(&self.strategy)
+    }
+
+    /// Replaces the exit strategy
+    pub fn set_strategy(&self, strategy: ExitStrategy) {
+        *lock(&self.strategy) = strategy;
+    }
+
+    /// Registers a hook to run before exiting. Hooks run once, most recently
+    /// registered first.
+    pub fn hook<F: FnMut() + Send + 'static>(&self, hook: F) {
+        lock(&self.hooks).push(Box::new(hook));
+    }
+
+    /// Registers a hook removing the file or directory at `path` if it exists
+    pub fn remove<P: Into<PathBuf>>(&self, path: P) {
+        let path = path.into();
+        self.hook(move || {
+            if let Ok(meta) = fs::symlink_metadata(&path) {
+                let _ = if meta.is_dir() { fs::remove_dir_all(&path) }
+                        else { fs::remove_file(&path) };
+            }
+        });
+    }
+
+    /// Returns the status of the last exit that did not terminate the process
+    pub fn status(&self) -> Option<ExitStatus> {
+        *lock(&self.status)
+    }
+
+    /// Runs and clears all registered hooks
+    pub fn run_hooks(&self) {
+        // Hooks are taken out first so that a hook may register another
+        let hooks: Vec<Hook> = lock(&self.hooks).drain(..).collect();
+        for mut hook in hooks.into_iter().rev() {
+            hook();
+        }
+    }
+
+    // Ends execution with `status` according to the strategy
+    pub(crate) fn finish(&self, status: ExitStatus) {
+        match self.strategy() {
+            ExitStrategy::Process => { process::exit(status.code()); },
+            ExitStrategy::Return => { *lock(&self.status) = Some(status); },
+            ExitStrategy::Unwind => {
+                *lock(&self.status) = Some(status);
+                panic::resume_unwind(Box::new(status));
+            },
+        }
+    }
+}
+
+impl Default for OnExit {
+    fn default() -> OnExit {
+        OnExit::new()
+    }
+}
+
+/// Runs `f`, returning the `ExitStatus` if it exits using
+/// `ExitStrategy::Unwind`. Panics that are not exits are resumed.
+///
+/// # Example
+///
+/// ```
+/// use rpf::{Prog,Exit,ExitStatus,ExitStrategy};
+///
+/// let prog = Prog::new("util", "0.1.0", "2015");
+/// prog.on_exit.set_strategy(ExitStrategy::Unwind);
+/// let res = rpf::catch_exit(|| prog.error("Some kind of error occured!", ExitStatus::Error));
+/// assert_eq!(res, Err(ExitStatus::Error));
+/// ```
+pub fn catch_exit<F: FnOnce() -> R, R>(f: F) -> Result<R, ExitStatus> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(r) => { Ok(r) },
+        Err(payload) => {
+            let payload: Box<dyn Any + Send> = payload;
+            match payload.downcast::<ExitStatus>() {
+                Ok(status) => { Err(*status) },
+                Err(payload) => { panic::resume_unwind(payload) },
+            }
+        },
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => { guard },
+        Err(poisoned) => { poisoned.into_inner() },
+    }
 }
 
 /// Causes a `Prog` struct to exit using an error and exit status
 pub trait Exit<T: AsRef<str>> {
-    /// Used for errors, prints error messages in red terminal font and calls
-    /// `rpf::Exit::exit`
+    /// Used for errors, prints error messages in red terminal font to the
+    /// `Prog`'s error stream and calls `rpf::Prog::exit`
     ///
     /// # Example
-    // Ignored here as doc-tests are ended because of `std::process::exit`
-    /// ```ignore
-    /// use rpf::{Prog,Exit,ExitStatus};
+    /// ```
+    /// use rpf::{Prog,Exit,ExitStatus,ExitStrategy};
     ///
-    /// let prog = Prog { name: "test", vers: "0.1.0", yr: "2015" };
+    /// let prog = Prog::new("test", "0.1.0", "2015");
+    /// prog.on_exit.set_strategy(ExitStrategy::Return);
     /// prog.error("Some kind of error occured!".to_string(), ExitStatus::Error);
+    /// assert_eq!(prog.on_exit.status(), Some(ExitStatus::Error));
     /// ```
     fn error(&self, mesg: T, status: ExitStatus);
 
@@ -40,14 +212,54 @@ pub trait Exit<T: AsRef<str>> {
 
 impl <T: AsRef<str>> Exit<T> for Prog {
     fn error(&self, mesg: T, status: ExitStatus) {
-        println!("{}{} {}", self.name.paint(Color::Red), ":".paint(Color::Red),
-        mesg.as_ref().paint(Color::Red));
-        &self.exit(status);
+        self.bars.clear(&self.io);
+        if self.format.get().is_json() {
+            self.json_error(mesg.as_ref(), status.code());
+        } else {
+            self.io.eprint(format_args!("{}{} {}\n", self.name.paint(Color::Red),
+            ":".paint(Color::Red), mesg.as_ref().paint(Color::Red)));
+        }
+        self.exit(status);
     }
 
      fn path_error(&self, mesg: T, item: PathBuf) {
-        println!("{}{} {}", item.as_str().paint(Color::Red),
-        ":".paint(Color::Red), mesg.as_ref().paint(Color::Red));
-        &self.exit(ExitStatus::Error);
+        self.bars.clear(&self.io);
+        if self.format.get().is_json() {
+            self.json_error(&format!("{}: {}", item.as_str(), mesg.as_ref()),
+                            ExitStatus::Error.code());
+        } else {
+            self.io.eprint(format_args!("{}{} {}\n", item.as_str().paint(Color::Red),
+            ":".paint(Color::Red), mesg.as_ref().paint(Color::Red)));
+        }
+        self.exit(ExitStatus::Error);
     }
 }
+
+#[test]
+fn test_status_exit_hooks() {
+    use std::sync::Arc;
+    use utils::Output;
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let (first, second) = (order.clone(), order.clone());
+    prog.on_exit.set_strategy(ExitStrategy::Return);
+    prog.on_exit.hook(move || first.lock().unwrap().push(1));
+    prog.on_exit.hook(move || second.lock().unwrap().push(2));
+    prog.error("failed", ExitStatus::OptError);
+    assert_eq!(prog.on_exit.status(), Some(ExitStatus::OptError));
+    assert_eq!(*order.lock().unwrap(), vec![2, 1]);
+    prog.exit(ExitStatus::Ok);
+    assert_eq!(*order.lock().unwrap(), vec![2, 1]);
+}
+
+#[test]
+fn test_status_catch_exit() {
+    use utils::Output;
+
+    let prog = Prog { io: Output::capture(), ..Prog::new("util", "0.1.0", "2015") };
+    prog.on_exit.set_strategy(ExitStrategy::Unwind);
+    assert_eq!(catch_exit(|| prog.prog_try()), Err(ExitStatus::ArgError));
+    assert_eq!(catch_exit(|| 1), Ok(1));
+    assert!(prog.io.take_err().contains("Missing arguments"));
+}
@@ -5,8 +5,32 @@
 extern crate ansi_term;
 
 use self::ansi_term::Style;
-use self::ansi_term::{Colour,ANSIString};
-use self::ansi_term::Colour::{Red};
+use self::ansi_term::Colour;
+
+use std::sync::atomic::{AtomicBool,Ordering};
+
+static COLOR: AtomicBool = AtomicBool::new(true);
+
+/// Enables or disables styling by `Styled` for the whole process, strings are
+/// printed without escape codes while it is disabled. See `Output::set_color`
+/// to disable colour for a single `Prog`.
+///
+/// # Example
+/// ```
+/// use rpf::Styled;
+///
+/// rpf::set_color(false);
+/// assert_eq!("plain".bold().to_string(), "plain");
+/// rpf::set_color(true);
+/// ```
+pub fn set_color(enabled: bool) {
+    COLOR.store(enabled, Ordering::SeqCst);
+}
+
+/// Returns whether `Styled` adds escape codes
+pub fn color() -> bool {
+    COLOR.load(Ordering::SeqCst)
+}
 
 /// Wrapper for `ansi_term::Colour`
 pub enum Color {
@@ -30,7 +54,7 @@ pub trait Styled {
     ///
     /// println!("{}", "Styled string test".bold());
     /// ```
-    fn bold(&self) -> ansi_term::ANSIString;
+    fn bold(&self) -> ansi_term::ANSIString<'_>;
 
     /// Creates an underlined `ANSIString`
     ///
@@ -40,7 +64,7 @@ pub trait Styled {
     ///
     /// println!("{}", "Styled string test".underline());
     /// ```
-    fn underline(&self) -> ansi_term::ANSIString;
+    fn underline(&self) -> ansi_term::ANSIString<'_>;
 
     /// Paints a given string with the color given
     ///
@@ -51,28 +75,31 @@ pub trait Styled {
     ///
     /// println!("{}", "Styled string test".paint(Color::Yellow));
     /// ```
-    fn paint(&self, color: Color) -> ansi_term::ANSIString;
+    fn paint(&self, color: Color) -> ansi_term::ANSIString<'_>;
 }
 
 impl<T: AsRef<str>> Styled for T {
-    fn bold(&self) -> ansi_term::ANSIString {
-        Style::default().bold().paint(&self.as_ref())
+    fn bold(&self) -> ansi_term::ANSIString<'_> {
+        if !self::color() { return Style::default().paint(self.as_ref()); }
+        Style::default().bold().paint(self.as_ref())
     }
 
-    fn underline(&self) -> ansi_term::ANSIString {
-        Style::default().underline().paint(&self.as_ref())
+    fn underline(&self) -> ansi_term::ANSIString<'_> {
+        if !self::color() { return Style::default().paint(self.as_ref()); }
+        Style::default().underline().paint(self.as_ref())
     }
 
-    fn paint(&self, color: Color) -> ansi_term::ANSIString {
+    fn paint(&self, color: Color) -> ansi_term::ANSIString<'_> {
+        if !self::color() { return Style::default().paint(self.as_ref()); }
         match color {
-            Color::Black   => { Colour::Black.paint(&self.as_ref()) },
-            Color::Red     => { Colour::Red.paint(&self.as_ref()) },
-            Color::Green   => { Colour::Green.paint(&self.as_ref()) },
-            Color::Yellow  => { Colour::Yellow.paint(&self.as_ref()) },
-            Color::Blue    => { Colour::Blue.paint(&self.as_ref()) },
-            Color::Purple  => { Colour::Purple.paint(&self.as_ref()) },
-            Color::Cyan    => { Colour::Cyan.paint(&self.as_ref()) },
-            Color::White   => { Colour::White.paint(&self.as_ref()) },
+            Color::Black   => { Colour::Black.paint(self.as_ref()) },
+            Color::Red     => { Colour::Red.paint(self.as_ref()) },
+            Color::Green   => { Colour::Green.paint(self.as_ref()) },
+            Color::Yellow  => { Colour::Yellow.paint(self.as_ref()) },
+            Color::Blue    => { Colour::Blue.paint(self.as_ref()) },
+            Color::Purple  => { Colour::Purple.paint(self.as_ref()) },
+            Color::Cyan    => { Colour::Cyan.paint(self.as_ref()) },
+            Color::White   => { Colour::White.paint(self.as_ref()) },
         }
     }
 }
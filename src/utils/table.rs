@@ -0,0 +1,288 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Tables and `ls` style columns
+//!
+//! Cells are measured with `text::width`, so cells styled with `Styled` and
+//! East Asian wide characters line up.
+//!
+//! ```
+//! use rpf::table::{Align,Table};
+//!
+//! let table = Table::new()
+//!     .header(&["NAME", "SIZE"])
+//!     .row(&["a.txt", "12"])
+//!     .row(&["漢字.txt", "3400"])
+//!     .align(1, Align::Right);
+//! assert_eq!(table.render(), "NAME      SIZE\n\
+//!                             a.txt       12\n\
+//!                             漢字.txt  3400\n");
+//! ```
+
+use utils::Prog;
+use utils::term;
+use utils::text;
+
+const MIN_WIDTH: usize = 3;
+
+/// How a column's cells are aligned
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/// What happens to cells wider than their column
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum Overflow {
+    /// Cells are cut short and end with `…`
+    Truncate,
+    /// Cells are wrapped onto several lines
+    Wrap,
+}
+
+/// A table of text
+#[derive(Clone,Debug)]
+pub struct Table {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+    align: Vec<Align>,
+    border: bool,
+    max_width: Option<usize>,
+    overflow: Overflow,
+}
+
+impl Table {
+    /// Creates an empty table without borders or a width limit
+    pub fn new() -> Table {
+        Table { header: Vec::new(), rows: Vec::new(), align: Vec::new(), border: false,
+                max_width: None, overflow: Overflow::Truncate }
+    }
+
+    /// Sets the header row
+    pub fn header<T: AsRef<str>>(mut self, cells: &[T]) -> Table {
+        self.header = cells.iter().map(|c| c.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Adds a row
+    pub fn row<T: AsRef<str>>(mut self, cells: &[T]) -> Table {
+        self.push(cells);
+        self
+    }
+
+    /// Adds a row to a table that is being filled in a loop
+    pub fn push<T: AsRef<str>>(&mut self, cells: &[T]) {
+        self.rows.push(cells.iter().map(|c| c.as_ref().to_string()).collect());
+    }
+
+    /// Sets the alignment of column `col`, columns are aligned left by default
+    pub fn align(mut self, col: usize, align: Align) -> Table {
+        if self.align.len() <= col {
+            self.align.resize(col + 1, Align::Left);
+        }
+        self.align[col] = align;
+        self
+    }
+
+    /// Draws borders around cells
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::table::Table;
+    ///
+    /// let table = Table::new().header(&["A", "B"]).row(&["1", "2"]).border(true);
+    /// assert_eq!(table.render(), "+---+---+\n\
+    ///                             | A | B |\n\
+    ///                             +---+---+\n\
+    ///                             | 1 | 2 |\n\
+    ///                             +---+---+\n");
+    /// ```
+    pub fn border(mut self, border: bool) -> Table {
+        self.border = border;
+        self
+    }
+
+    /// Limits the table to `width` columns, shrinking the widest columns
+    /// first
+    pub fn max_width(mut self, width: usize) -> Table {
+        self.max_width = Some(width);
+        self
+    }
+
+    /// Sets what happens to cells wider than their column
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::table::{Overflow,Table};
+    ///
+    /// let table = Table::new().row(&["id", "a long description"]).max_width(16);
+    /// assert_eq!(table.clone().render(), "id  a long desc…\n");
+    /// assert_eq!(table.overflow(Overflow::Wrap).render(), "id  a long\n    description\n");
+    /// ```
+    pub fn overflow(mut self, overflow: Overflow) -> Table {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Returns the table as text, each line ending with a newline
+    pub fn render(&self) -> String {
+        let ncols = self.rows.iter().map(|r| r.len()).chain(Some(self.header.len()))
+            .max().unwrap_or(0);
+        if ncols == 0 { return String::new(); }
+        let mut widths = vec![0; ncols];
+        for row in self.rows.iter().chain(Some(&self.header)) {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(text::width(cell));
+            }
+        }
+        if let Some(max) = self.max_width {
+            let overhead = if self.border { 3 * ncols + 1 } else { 2 * (ncols - 1) };
+            let avail = max.saturating_sub(overhead);
+            while widths.iter().sum::<usize>() > avail {
+                let (i, &w) = widths.iter().enumerate().max_by_key(|c| *c.1).unwrap();
+                if w <= MIN_WIDTH { break; }
+                widths[i] -= 1;
+            }
+        }
+        let rule = if self.border {
+            let dashes: Vec<String> = widths.iter().map(|w| "-".repeat(w + 2)).collect();
+            format!("+{}+\n", dashes.join("+"))
+        } else {
+            String::new()
+        };
+        let mut out = rule.clone();
+        if !self.header.is_empty() {
+            out.push_str(&self.render_row(&self.header, &widths));
+            out.push_str(&rule);
+        }
+        for row in self.rows.iter() {
+            out.push_str(&self.render_row(row, &widths));
+        }
+        if !self.rows.is_empty() { out.push_str(&rule); }
+        out
+    }
+
+    fn render_row(&self, row: &[String], widths: &[usize]) -> String {
+        let cells: Vec<Vec<String>> = widths.iter().enumerate().map(|(i, &w)| {
+            let cell = row.get(i).map(|c| &c[..]).unwrap_or("");
+            match self.overflow {
+                Overflow::Truncate => { vec![text::truncate(cell, w)] },
+                Overflow::Wrap => { text::wrap(cell, w) },
+            }
+        }).collect();
+        let height = cells.iter().map(|c| c.len()).max().unwrap_or(1);
+        let mut out = String::new();
+        for line in 0..height {
+            let parts: Vec<String> = cells.iter().enumerate().map(|(i, c)| {
+                let cell = c.get(line).map(|s| &s[..]).unwrap_or("");
+                align(cell, widths[i], self.align.get(i).cloned().unwrap_or(Align::Left))
+            }).collect();
+            let text = if self.border { format!("| {} |", parts.join(" | ")) }
+                       else { parts.join("  ").trim_end().to_string() };
+            out.push_str(&text);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+impl Default for Table {
+    fn default() -> Table {
+        Table::new()
+    }
+}
+
+fn align(cell: &str, w: usize, align: Align) -> String {
+    let space = w.saturating_sub(text::width(cell));
+    match align {
+        Align::Left => { format!("{}{}", cell, " ".repeat(space)) },
+        Align::Right => { format!("{}{}", " ".repeat(space), cell) },
+        Align::Center => {
+            format!("{}{}{}", " ".repeat(space / 2), cell, " ".repeat(space - space / 2))
+        },
+    }
+}
+
+/// Packs `items` into as many columns as fit in `width`, filling each column
+/// top to bottom like `ls`
+///
+/// # Example
+/// ```
+/// use rpf::table;
+///
+/// let names = ["a", "bb", "ccc", "d", "ee"];
+/// assert_eq!(table::columns(&names, 12), "a   ccc  ee\nbb  d\n");
+/// assert_eq!(table::columns(&names, 80), "a  bb  ccc  d  ee\n");
+/// ```
+pub fn columns<T: AsRef<str>>(items: &[T], width: usize) -> String {
+    let n = items.len();
+    let widths: Vec<usize> = items.iter().map(|i| text::width(i.as_ref())).collect();
+    for want in (1..=n).rev() {
+        let rows = n.div_ceil(want);
+        let cols = n.div_ceil(rows);
+        let col_w: Vec<usize> = (0..cols)
+            .map(|c| widths[c * rows..n.min((c + 1) * rows)].iter().cloned().max().unwrap_or(0))
+            .collect();
+        if col_w.iter().sum::<usize>() + 2 * (cols - 1) > width && rows < n { continue; }
+        let mut out = String::new();
+        for r in 0..rows {
+            let mut line = String::new();
+            for (c, w) in col_w.iter().enumerate() {
+                if let Some(item) = items.get(c * rows + r) {
+                    line.push_str(&text::pad(item.as_ref(), w + 2));
+                }
+            }
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        return out;
+    }
+    String::new()
+}
+
+impl Prog {
+    /// Prints a table, limited to the terminal's width when printing to a
+    /// terminal and no width was set
+    pub fn table(&self, table: &Table) {
+        if table.max_width.is_none() && self.io.out_is_tty() {
+            let table = table.clone().max_width(term::width());
+            self.io.print(format_args!("{}", table.render()));
+        } else {
+            self.io.print(format_args!("{}", table.render()));
+        }
+    }
+
+    /// Prints `items` packed into columns like `ls`, see `columns`
+    pub fn columns<T: AsRef<str>>(&self, items: &[T]) {
+        let width = if self.io.out_is_tty() { term::width() } else { term::DEFAULT_WIDTH };
+        self.io.print(format_args!("{}", columns(items, width)));
+    }
+}
+
+#[test]
+fn test_table_styled_cells() {
+    use utils::{Color,Styled};
+
+    let red = "bad".paint(Color::Red).to_string();
+    let table = Table::new().row(&[&red[..], "x"]).row(&["good", "y"]);
+    let out = table.render();
+    assert_eq!(text::strip_ansi(&out), "bad   x\ngood  y\n");
+}
+
+#[test]
+fn test_table_shrink() {
+    let table = Table::new().row(&["abcdefgh", "abcdefgh"]).max_width(12).border(true);
+    assert_eq!(table.render(), "+-----+-----+\n| ab… | ab… |\n+-----+-----+\n");
+    let table = Table::new().row(&["abcdef", "ab"]).max_width(1);
+    assert_eq!(table.render(), "ab…  ab\n");
+}
+
+#[test]
+fn test_table_columns_edge() {
+    let none: [&str; 0] = [];
+    assert_eq!(columns(&none, 80), "");
+    assert_eq!(columns(&["toolong"], 3), "toolong\n");
+}
@@ -0,0 +1,231 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Terminal detection and modes
+
+#[cfg(unix)]
+extern crate libc;
+
+use std::env;
+use std::io;
+use std::sync::atomic::{AtomicBool,AtomicUsize,Ordering};
+
+/// Width used when the terminal's size is unknown
+pub const DEFAULT_WIDTH: usize = 80;
+/// Height used when the terminal's size is unknown
+pub const DEFAULT_HEIGHT: usize = 24;
+
+static RESIZES: AtomicUsize = AtomicUsize::new(0);
+static WATCHING: AtomicBool = AtomicBool::new(false);
+
+/// File descriptor of standard input
+pub const STDIN: i32 = 0;
+/// File descriptor of standard output
+pub const STDOUT: i32 = 1;
+/// File descriptor of standard error
+pub const STDERR: i32 = 2;
+
+/// Returns true if `fd` is a terminal
+///
+/// # Example
+/// ```
+/// use rpf::term;
+///
+/// if term::is_tty(term::STDOUT) {
+///     println!("writing to a terminal");
+/// }
+/// ```
+#[cfg(unix)]
+pub fn is_tty(fd: i32) -> bool {
+    unsafe { libc::isatty(fd) == 1 }
+}
+
+/// Returns true if `fd` is a terminal
+#[cfg(not(unix))]
+pub fn is_tty(_fd: i32) -> bool {
+    false
+}
+
+/// Returns the terminal's width and height in columns and lines, `None` if
+/// none of the standard streams is a terminal. The `COLUMNS` and `LINES`
+/// environment variables take precedence over the size reported by the
+/// terminal.
+///
+/// # Example
+/// ```
+/// use rpf::term;
+///
+/// std::env::set_var("COLUMNS", "132");
+/// std::env::set_var("LINES", "50");
+/// assert_eq!(term::size(), Some((132, 50)));
+/// ```
+pub fn size() -> Option<(usize, usize)> {
+    let var = |name| env::var(name).ok().and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|&n| n > 0);
+    let reported = [STDOUT, STDERR, STDIN].iter().filter_map(|&fd| ioctl_size(fd)).next();
+    match (var("COLUMNS"), var("LINES"), reported) {
+        (Some(w), Some(h), _) => { Some((w, h)) },
+        (w, h, Some((rw, rh))) => { Some((w.unwrap_or(rw), h.unwrap_or(rh))) },
+        (Some(w), None, None) => { Some((w, DEFAULT_HEIGHT)) },
+        (None, Some(h), None) => { Some((DEFAULT_WIDTH, h)) },
+        (None, None, None) => { None },
+    }
+}
+
+/// Returns the terminal's width, `DEFAULT_WIDTH` if it is unknown
+pub fn width() -> usize {
+    size().map(|s| s.0).unwrap_or(DEFAULT_WIDTH)
+}
+
+/// Returns the terminal's height, `DEFAULT_HEIGHT` if it is unknown
+pub fn height() -> usize {
+    size().map(|s| s.1).unwrap_or(DEFAULT_HEIGHT)
+}
+
+#[cfg(unix)]
+fn ioctl_size(fd: i32) -> Option<(usize, usize)> {
+    unsafe {
+        let mut ws: libc::winsize = ::std::mem::zeroed();
+        if libc::ioctl(fd, libc::TIOCGWINSZ, &mut ws) == 0 && ws.ws_col > 0 && ws.ws_row > 0 {
+            Some((ws.ws_col as usize, ws.ws_row as usize))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn ioctl_size(_fd: i32) -> Option<(usize, usize)> {
+    None
+}
+
+#[cfg(unix)]
+extern "C" fn on_winch(_: libc::c_int) {
+    RESIZES.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Notices when the terminal is resized, by counting `SIGWINCH` signals
+///
+/// # Example
+/// ```no_run
+/// use rpf::term;
+///
+/// let mut resize = term::Resize::watch();
+/// loop {
+///     if resize.changed() {
+///         println!("now {} columns wide", term::width());
+///     }
+///     # break;
+/// }
+/// ```
+pub struct Resize {
+    seen: usize,
+}
+
+impl Resize {
+    /// Starts watching for resizes, installing the `SIGWINCH` handler the
+    /// first time it is called
+    pub fn watch() -> Resize {
+        #[cfg(unix)]
+        {
+            if !WATCHING.swap(true, Ordering::SeqCst) {
+                let handler = on_winch as extern "C" fn(libc::c_int);
+                unsafe { libc::signal(libc::SIGWINCH, handler as libc::sighandler_t); }
+            }
+        }
+        #[cfg(not(unix))]
+        WATCHING.store(true, Ordering::SeqCst);
+        Resize { seen: RESIZES.load(Ordering::SeqCst) }
+    }
+
+    /// Returns true if the terminal was resized since the last call
+    pub fn changed(&mut self) -> bool {
+        let now = RESIZES.load(Ordering::SeqCst);
+        let changed = now != self.seen;
+        self.seen = now;
+        changed
+    }
+}
+
+/// How input is read from the terminal while a `RawMode` is held
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum Mode {
+    /// Lines are read as usual but typed characters are not shown. Ctrl-C
+    /// ends the line and is read as a character instead of sending `SIGINT`,
+    /// so the terminal is always restored.
+    NoEcho,
+    /// Keys are read one at a time as they are pressed and not shown
+    Raw,
+}
+
+/// Changes the mode of a terminal, restoring it when dropped
+pub struct RawMode {
+    #[cfg(unix)]
+    fd: i32,
+    #[cfg(unix)]
+    orig: libc::termios,
+}
+
+impl RawMode {
+    /// Switches `fd` to `mode`
+    #[cfg(unix)]
+    pub fn enable(fd: i32, mode: Mode) -> io::Result<RawMode> {
+        unsafe {
+            let mut orig: libc::termios = ::std::mem::zeroed();
+            if libc::tcgetattr(fd, &mut orig) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut raw = orig;
+            raw.c_lflag &= !(libc::ECHO | libc::ISIG);
+            if mode == Mode::NoEcho {
+                raw.c_cc[libc::VEOL] = 3;
+            } else {
+                raw.c_lflag &= !libc::ICANON;
+                raw.c_cc[libc::VMIN] = 1;
+                raw.c_cc[libc::VTIME] = 0;
+            }
+            if libc::tcsetattr(fd, libc::TCSAFLUSH, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(RawMode { fd, orig })
+        }
+    }
+
+    /// Switches `fd` to `mode`
+    #[cfg(not(unix))]
+    pub fn enable(_fd: i32, _mode: Mode) -> io::Result<RawMode> {
+        Err(io::Error::new(io::ErrorKind::Other, "terminal modes are not supported"))
+    }
+}
+
+impl Drop for RawMode {
+    #[cfg(unix)]
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(self.fd, libc::TCSAFLUSH, &self.orig); }
+    }
+
+    #[cfg(not(unix))]
+    fn drop(&mut self) {}
+}
+
+#[test]
+#[cfg(unix)]
+fn test_term_resize() {
+    let mut resize = Resize::watch();
+    assert!(!resize.changed());
+    unsafe { libc::raise(libc::SIGWINCH); }
+    assert!(resize.changed());
+    assert!(!resize.changed());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_term_not_tty() {
+    use std::fs::File;
+    use std::os::unix::io::AsRawFd;
+
+    let file = File::open("Cargo.toml").unwrap();
+    assert!(!is_tty(file.as_raw_fd()));
+    assert!(RawMode::enable(file.as_raw_fd(), Mode::Raw).is_err());
+}
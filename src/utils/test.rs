@@ -3,16 +3,51 @@
 // BSD 3-Clause license. For full terms please see the LICENSE file.
 
 use utils::PathMod;
+use utils::args;
 use utils::Styled;
 use utils::Color;
+use utils::Output;
 
+use std::cmp;
+use std::env;
 use std::fs;
+use std::io::{self,Read,Write};
 #[cfg(target_family = "unix")]
 use std::os;
-use std::path::{Path};
+use std::path::{Path,PathBuf,MAIN_SEPARATOR};
+use std::process::{self,Command};
+use std::sync::atomic::{AtomicUsize,Ordering};
 #[cfg(target_family = "windows")]
 use std::os::windows;
 
+static OUTPUT: Output = Output::std();
+
+// Prints a line through the test module's `Output`. While it writes to
+// standard output `println!` is used, so that the test harness captures it.
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        if OUTPUT.out_is_stdout() { println!($($arg)*); }
+        else { OUTPUT.print(format_args!("{}\n", format_args!($($arg)*))); }
+    };
+}
+
+/// Returns the `Output` that the test helpers print through, which may be
+/// redirected to capture what they print
+///
+/// # Example
+/// ```
+/// use rpf::test;
+/// use rpf::Stream;
+///
+/// test::output().set_out(Stream::Buffer(Vec::new()));
+/// test::create_file("output.txt");
+/// test::remove("output.txt");
+/// assert!(test::output().take_out().contains("created file"));
+/// ```
+pub fn output() -> &'static Output {
+    &OUTPUT
+}
+
 /// Removes a directory or file and all of its contents.
 ///
 /// # Example
@@ -25,21 +60,21 @@ pub fn remove<F: AsRef<Path>>(path: F) {
     match fs::symlink_metadata(path.as_ref()) {
         Ok(file) => {
             if file.file_type().is_dir() {
-                print!("{} is dir or symlink", path.as_ref().display());
+                outln!("{} is dir or symlink", path.as_ref().display());
                 match fs::remove_dir_all(&path) {
-                    Ok(_) => { println!("test: removed directory '{}'",
+                    Ok(_) => { outln!("test: removed directory '{}'",
                                         path.as_ref().as_str().paint(Color::Green))
                     },
-                    Err(_) => { println!("test: unable to remove directory '{}'",
+                    Err(_) => { outln!("test: unable to remove directory '{}'",
                                          path.as_ref().as_str().paint(Color::Red))
                     },
                 };
             } else if file.file_type().is_file() || file.file_type().is_symlink() {
                 match fs::remove_file(&path) {
-                    Ok(_) => { println!("test: removed file '{}'",
+                    Ok(_) => { outln!("test: removed file '{}'",
                                         path.as_ref().as_str().paint(Color::Green))
                     },
-                    Err(_) => { println!("test: unable to remove file '{}'",
+                    Err(_) => { outln!("test: unable to remove file '{}'",
                                          path.as_ref().as_str().paint(Color::Red))
                     },
                 };
@@ -60,9 +95,9 @@ pub fn remove<F: AsRef<Path>>(path: F) {
 /// ```
 pub fn create_file<F: AsRef<Path>>(path: F) {
     match fs::File::create(&path) {
-        Ok(_) => { println!("test: created file '{}'",
+        Ok(_) => { outln!("test: created file '{}'",
                             path.as_ref().as_str().paint(Color::Green)) },
-        Err(_) => { println!("test: unable to crate file '{}'",
+        Err(_) => { outln!("test: unable to crate file '{}'",
                             path.as_ref().as_str().paint(Color::Red)) },
     }
 }
@@ -78,18 +113,18 @@ pub fn create_file<F: AsRef<Path>>(path: F) {
 /// ```
 pub fn create_dir<F: AsRef<Path>>(path: F) {
     match fs::create_dir(&path) {
-        Ok(_) => { println!("test: created directory '{}'",
+        Ok(_) => { outln!("test: created directory '{}'",
                             path.as_ref().as_str().paint(Color::Green)) },
-        Err(_) => { println!("test: unable to crate directory '{}'",
+        Err(_) => { outln!("test: unable to crate directory '{}'",
                             path.as_ref().as_str().paint(Color::Red)) },
     }
 }
 
 #[cfg(target_family = "unix")]
 pub fn create_symlink<T: AsRef<Path>, F: AsRef<Path>>(from: &F, to: &T) {
-    match os::unix::fs::symlink(&from, &to) {
+    match os::unix::fs::symlink(from, to) {
         Ok(_) => {
-            println!("{} symlinked to {}", from.as_ref().as_str().bold(),
+            outln!("{} symlinked to {}", from.as_ref().as_str().bold(),
                 to.as_ref().as_str().bold());
         },
         Err(e) => {
@@ -105,7 +140,7 @@ pub fn create_symlink<T: AsRef<Path>, F: AsRef<Path>>(from: &F, to: &T) {
             if file.file_type().is_file() {
                 match windows::fs::symlink_file(&from, &to) {
                     Ok(_) => {
-                        println!("{} symlinked to {}", from.as_ref().as_str().bold(),
+                        outln!("{} symlinked to {}", from.as_ref().as_str().bold(),
                         to.as_ref().as_str().bold());
                     },
                     Err(e) => {
@@ -115,7 +150,7 @@ pub fn create_symlink<T: AsRef<Path>, F: AsRef<Path>>(from: &F, to: &T) {
             } else if file.is_dir() {
                 match windows::fs::symlink_dir(&from, &to) {
                     Ok(_) => {
-                        println!("{} symlinked to {}", from.as_ref().as_str().bold(),
+                        outln!("{} symlinked to {}", from.as_ref().as_str().bold(),
                         to.as_ref().as_str().bold());
                     },
                     Err(e) => {
@@ -128,6 +163,510 @@ pub fn create_symlink<T: AsRef<Path>, F: AsRef<Path>>(from: &F, to: &T) {
     };
 }
 
+/// Environment variable that causes `Snapshot` to rewrite stored snapshots
+/// instead of comparing against them when set to anything other than "0"
+pub const UPDATE_SNAPSHOTS: &str = "RPF_UPDATE_SNAPSHOTS";
+
+/// Golden-file comparison of captured output against stored `.snap` files
+///
+/// Output is passed through the snapshot's redaction filters before being
+/// compared, so values that change between runs such as timestamps and
+/// temporary paths can be masked out.
+///
+/// # Example
+/// ```
+/// use rpf::test;
+/// use rpf::test::Snapshot;
+///
+/// let snap = Snapshot::new("snap-doc").redact_timestamps();
+/// std::env::set_var(test::UPDATE_SNAPSHOTS, "1");
+/// snap.assert("banner", "util 0.1.0, built 2015-08-01 12:00:00\n");
+/// std::env::remove_var(test::UPDATE_SNAPSHOTS);
+/// snap.assert("banner", "util 0.1.0, built 2016-01-20 08:30:12\n");
+/// test::remove("snap-doc");
+/// ```
+pub struct Snapshot {
+    dir: PathBuf,
+    filters: Vec<Filter>,
+}
+
+// A redaction filter applied to output before comparison
+type Filter = Box<dyn Fn(&str) -> String>;
+
+impl Snapshot {
+    /// Creates a `Snapshot` storing its `.snap` files in `dir`
+    pub fn new<P: AsRef<Path>>(dir: P) -> Snapshot {
+        Snapshot { dir: dir.as_ref().to_path_buf(), filters: Vec::new() }
+    }
+
+    /// Adds a redaction filter, filters are applied in the order they are added
+    pub fn filter<F: Fn(&str) -> String + 'static>(mut self, filter: F) -> Snapshot {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Replaces every occurrence of `from` with `to`
+    pub fn redact<T: AsRef<str>>(self, from: T, to: T) -> Snapshot {
+        let (from, to) = (from.as_ref().to_string(), to.as_ref().to_string());
+        self.filter(move |s| {
+            if from.is_empty() { s.to_string() } else { s.replace(&from, &to) }
+        })
+    }
+
+    /// Replaces the system temporary directory with `[TEMP]`
+    pub fn redact_temp(self) -> Snapshot {
+        let temp = env::temp_dir();
+        let mut dirs = vec![temp.as_string()];
+        if let Ok(real) = fs::canonicalize(&temp) {
+            dirs.insert(0, real.as_string());
+        }
+        self.filter(move |s| {
+            let mut out = s.to_string();
+            for dir in dirs.iter() {
+                let dir = dir.trim_end_matches(MAIN_SEPARATOR);
+                if !dir.is_empty() { out = out.replace(dir, "[TEMP]"); }
+            }
+            out
+        })
+    }
+
+    /// Replaces dates (`2015-08-01`) with `[DATE]` and times (`12:00:00`,
+    /// optionally with fractional seconds) with `[TIME]`
+    pub fn redact_timestamps(self) -> Snapshot {
+        self.filter(|s| redact_pattern(&redact_pattern(s, "dddd-dd-dd", "[DATE]", false),
+                                       "dd:dd:dd", "[TIME]", true))
+    }
+
+    /// Returns the path of the snapshot file used for `name`
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.snap", name))
+    }
+
+    /// Compares `actual` against the stored snapshot `name`, returning a
+    /// unified diff of the differences on mismatch. When `UPDATE_SNAPSHOTS` is
+    /// set the snapshot is written instead.
+    pub fn check<T: AsRef<str>>(&self, name: &str, actual: T) -> Result<(), String> {
+        let actual = self.apply(actual.as_ref());
+        let path = self.path(name);
+        if updating() {
+            if let Err(e) = fs::create_dir_all(&self.dir)
+                .and_then(|_| fs::File::create(&path))
+                .and_then(|mut f| f.write_all(actual.as_bytes())) {
+                return Err(format!("test: unable to write snapshot '{}': {}",
+                                   path.as_str(), e));
+            }
+            outln!("test: updated snapshot '{}'", path.as_str().paint(Color::Green));
+            return Ok(());
+        }
+        let mut expected = String::new();
+        if let Err(e) = fs::File::open(&path).and_then(|mut f| f.read_to_string(&mut expected)) {
+            return Err(format!("test: unable to read snapshot '{}': {}\n\
+                                Set {}=1 to create it", path.as_str(), e, UPDATE_SNAPSHOTS));
+        }
+        if expected == actual { Ok(()) } else { Err(diff(&expected, &actual)) }
+    }
+
+    /// Like `check` but prints the diff and panics on mismatch
+    pub fn assert<T: AsRef<str>>(&self, name: &str, actual: T) {
+        if let Err(e) = self.check(name, actual) {
+            outln!("{}", e);
+            panic!("snapshot '{}' does not match", name);
+        }
+    }
+
+    fn apply(&self, s: &str) -> String {
+        self.filters.iter().fold(s.to_string(), |acc, f| f(&acc))
+    }
+}
+
+/// Compares `actual` against the snapshot file at `path`, panicking with a
+/// diff on mismatch
+///
+/// # Example
+/// ```
+/// use rpf::test;
+///
+/// std::env::set_var(test::UPDATE_SNAPSHOTS, "1");
+/// test::assert_snapshot("help.snap", "usage: util [OPTIONS]\n");
+/// std::env::remove_var(test::UPDATE_SNAPSHOTS);
+/// test::assert_snapshot("help.snap", "usage: util [OPTIONS]\n");
+/// test::remove("help.snap");
+/// ```
+pub fn assert_snapshot<P: AsRef<Path>, T: AsRef<str>>(path: P, actual: T) {
+    let path = path.as_ref();
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let name = match path.file_stem() {
+        Some(s) => { s.to_string_lossy().into_owned() },
+        None => { panic!("test: '{}' is not a snapshot file", path.as_str()); },
+    };
+    Snapshot::new(dir).assert(&name, actual);
+}
+
+/// Runs shell-session transcripts against real commands inside a fixture
+/// workspace, a fresh directory created for each run
+///
+/// A transcript is a text file of commands prefixed with `$ `, each followed
+/// by the output it is expected to print (standard output, then standard
+/// error) and optionally `? N` giving the expected exit status, which
+/// otherwise defaults to 0. Text before the first command is ignored and may
+/// be used to describe the test. Commands are split using shell quoting rules
+/// but are not run through a shell, and occurrences of the workspace path in
+/// output are replaced with `[WORK]`.
+///
+/// ```text
+/// Listing an empty directory prints nothing
+/// $ ls
+/// $ util --bad-flag
+/// util: Unknown option '--bad-flag'
+/// ? 2
+/// ```
+///
+/// # Example
+/// ```
+/// use rpf::test::Transcript;
+///
+/// # if cfg!(target_family = "unix") {
+/// Transcript::new("transcript-doc")
+///     .file("greeting.txt", "hello\n")
+///     .assert_str("$ cat greeting.txt\nhello\n$ false\n? 1\n");
+/// # }
+/// ```
+pub struct Transcript {
+    parent: PathBuf,
+    bins: Vec<(String, PathBuf)>,
+    vars: Vec<(String, String)>,
+    files: Vec<(PathBuf, String)>,
+}
+
+/// A single command from a transcript and what it is expected to produce
+#[derive(Debug,PartialEq)]
+pub struct TranscriptCase {
+    /// Line of the transcript the command appeared on
+    pub line: usize,
+    /// Command line as written after `$ `
+    pub command: String,
+    /// Expected output
+    pub output: String,
+    /// Expected exit status
+    pub status: i32,
+}
+
+impl Transcript {
+    /// Creates a `Transcript` whose commands run in a new directory inside
+    /// `parent`, created before and removed after each run. Nothing else in
+    /// `parent` is touched, and `parent` is only removed if it was created for
+    /// the run and is left empty.
+    pub fn new<P: AsRef<Path>>(parent: P) -> Transcript {
+        Transcript {
+            parent: parent.as_ref().to_path_buf(),
+            bins: Vec::new(),
+            vars: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Runs `path` whenever a command names `name`, useful for pointing at a
+    /// binary built by cargo such as `env!("CARGO_BIN_EXE_util")`
+    pub fn bin<P: AsRef<Path>>(mut self, name: &str, path: P) -> Transcript {
+        self.bins.push((name.to_string(), path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Sets an environment variable for every command
+    pub fn env(mut self, key: &str, val: &str) -> Transcript {
+        self.vars.push((key.to_string(), val.to_string()));
+        self
+    }
+
+    /// Adds a fixture file, relative to the workspace, created before running
+    pub fn file<P: AsRef<Path>>(mut self, path: P, contents: &str) -> Transcript {
+        self.files.push((path.as_ref().to_path_buf(), contents.to_string()));
+        self
+    }
+
+    /// Parses transcript text into the commands it contains
+    ///
+    /// # Example
+    /// ```
+    /// use rpf::test::Transcript;
+    ///
+    /// let cases = Transcript::parse("Intro\n$ util -v\nutil 0.1.0\n$ util -x\n? 2\n");
+    /// assert_eq!(cases.len(), 2);
+    /// assert_eq!(cases[0].output, "util 0.1.0\n");
+    /// assert_eq!(cases[1].status, 2);
+    /// ```
+    pub fn parse(text: &str) -> Vec<TranscriptCase> {
+        let mut cases: Vec<TranscriptCase> = Vec::new();
+        for (num, line) in text.lines().enumerate() {
+            if let Some(cmd) = line.strip_prefix("$ ") {
+                cases.push(TranscriptCase { line: num + 1, command: cmd.to_string(),
+                                            output: String::new(), status: 0 });
+            } else if let Some(case) = cases.last_mut() {
+                match line.strip_prefix("? ").map(|s| s.trim().parse::<i32>()) {
+                    Some(Ok(status)) => { case.status = status; },
+                    _ => { case.output.push_str(line); case.output.push('\n'); },
+                }
+            }
+        }
+        for case in cases.iter_mut() {
+            trim_newlines(&mut case.output);
+        }
+        cases
+    }
+
+    /// Runs every command in the transcript file at `path`, returning a report
+    /// of each command whose output or exit status did not match
+    pub fn run_file<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let mut text = String::new();
+        if let Err(e) = fs::File::open(&path).and_then(|mut f| f.read_to_string(&mut text)) {
+            return Err(format!("test: unable to read transcript '{}': {}",
+                               path.as_ref().as_str(), e));
+        }
+        self.run_str(&text)
+    }
+
+    /// Runs every command in the transcript `text`, see `run_file`
+    pub fn run_str(&self, text: &str) -> Result<(), String> {
+        let made_parent = fs::symlink_metadata(&self.parent).is_err();
+        let result = self.create_workspace().and_then(|work| {
+            let result = self.run_in(&work, text);
+            remove(&work);
+            result
+        });
+        if made_parent { let _ = fs::remove_dir(&self.parent); }
+        result
+    }
+
+    // Creates a directory inside `parent` that no other run uses
+    fn create_workspace(&self) -> Result<PathBuf, String> {
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+        let fail = |path: &Path, e: io::Error| {
+            format!("test: unable to create directory '{}': {}", path.as_str(), e)
+        };
+        fs::create_dir_all(&self.parent).map_err(|e| fail(&self.parent, e))?;
+        loop {
+            let run = RUNS.fetch_add(1, Ordering::SeqCst);
+            let work = self.parent.join(format!("transcript-{}-{}", process::id(), run));
+            match fs::create_dir(&work) {
+                Ok(_) => { return Ok(work); },
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {},
+                Err(e) => { return Err(fail(&work, e)); },
+            }
+        }
+    }
+
+    // Creates the fixture files in `work` and runs each command there
+    fn run_in(&self, work: &Path, text: &str) -> Result<(), String> {
+        for (path, contents) in self.files.iter() {
+            let path = work.join(path);
+            if let Some(parent) = path.parent() {
+                if let Err(e) = fs::create_dir_all(parent) {
+                    return Err(format!("test: unable to create directory '{}': {}",
+                                       parent.as_str(), e));
+                }
+            }
+            if let Err(e) = fs::File::create(&path)
+                .and_then(|mut f| f.write_all(contents.as_bytes())) {
+                return Err(format!("test: unable to create file '{}': {}", path.as_str(), e));
+            }
+        }
+        let mut report = String::new();
+        for case in Transcript::parse(text).iter() {
+            if let Err(e) = self.run_case(case, work) {
+                report.push_str(&format!("{} line {}: $ {}\n{}", "test:".paint(Color::Red),
+                                         case.line, case.command, e));
+            }
+        }
+        if report.is_empty() { Ok(()) } else { Err(report) }
+    }
+
+    /// Like `run_file` but prints the report and panics on mismatch
+    pub fn assert_file<P: AsRef<Path>>(&self, path: P) {
+        if let Err(e) = self.run_file(&path) {
+            outln!("{}", e);
+            panic!("transcript '{}' does not match", path.as_ref().as_str());
+        }
+    }
+
+    /// Like `run_str` but prints the report and panics on mismatch
+    pub fn assert_str(&self, text: &str) {
+        if let Err(e) = self.run_str(text) {
+            outln!("{}", e);
+            panic!("transcript does not match");
+        }
+    }
+
+    fn run_case(&self, case: &TranscriptCase, work: &Path) -> Result<(), String> {
+        let argv = args::split(&case.command).map_err(|e| format!("{}\n", e))?;
+        let prog = match argv.first() {
+            Some(s) => { s },
+            None => { return Err("empty command\n".to_string()); },
+        };
+        let mut cmd = match self.bins.iter().find(|b| &b.0 == prog) {
+            Some(b) => { Command::new(&b.1) },
+            None => { Command::new(prog) },
+        };
+        cmd.args(&argv[1..]).current_dir(work);
+        for (key, val) in self.vars.iter() {
+            cmd.env(key, val);
+        }
+        let out = match cmd.output() {
+            Ok(out) => { out },
+            Err(e) => { return Err(format!("unable to run '{}': {}\n", prog, e)); },
+        };
+        let mut actual = String::from_utf8_lossy(&out.stdout).into_owned();
+        actual.push_str(&String::from_utf8_lossy(&out.stderr));
+        let work = fs::canonicalize(work).unwrap_or_else(|_| work.to_path_buf());
+        let mut actual = actual.replace(work.as_str(), "[WORK]");
+        trim_newlines(&mut actual);
+        let mut err = String::new();
+        if actual != case.output {
+            err.push_str(&diff(&case.output, &actual));
+        }
+        match out.status.code() {
+            Some(code) if code == case.status => {},
+            Some(code) => { err.push_str(&format!("expected exit status {}, got {}\n",
+                                                  case.status, code)) },
+            None => { err.push_str(&format!("expected exit status {}, terminated by signal\n",
+                                            case.status)) },
+        }
+        if err.is_empty() { Ok(()) } else { Err(err) }
+    }
+}
+
+// Ends `s` with exactly one newline, or none if it is empty, as transcript
+// output is compared
+fn trim_newlines(s: &mut String) {
+    let len = s.trim_end_matches('\n').len();
+    s.truncate(len);
+    if len > 0 { s.push('\n'); }
+}
+
+/// Returns a coloured unified diff between `old` and `new`, an empty string is
+/// returned when both are equal
+///
+/// # Example
+/// ```
+/// use rpf::test;
+///
+/// println!("{}", test::diff("a\nb\nc\n", "a\nB\nc\n"));
+/// assert_eq!(test::diff("same\n", "same\n"), "");
+/// ```
+pub fn diff(old: &str, new: &str) -> String {
+    const CONTEXT: usize = 3;
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let ops = diff_lines(&a, &b);
+    let mut out = String::new();
+    if ops.iter().all(|op| op.0 == ' ') {
+        if old != new {
+            out.push_str(&format!("{}\n", "trailing newline differs".paint(Color::Yellow)));
+        }
+        return out;
+    }
+    out.push_str(&format!("{}\n{}\n", "--- snapshot".paint(Color::Red),
+                          "+++ actual".paint(Color::Green)));
+    let mut i = 0;
+    while i < ops.len() {
+        if ops[i].0 == ' ' { i += 1; continue; }
+        // Extend the hunk while changes are separated by less than two contexts
+        let start = i.saturating_sub(CONTEXT);
+        let mut end = i;
+        let mut same = 0;
+        while end < ops.len() && same <= CONTEXT * 2 {
+            if ops[end].0 == ' ' { same += 1; } else { same = 0; }
+            end += 1;
+        }
+        end -= same.saturating_sub(CONTEXT);
+        let (mut a_pos, mut b_pos) = (ops[start].1, ops[start].2);
+        let a_len = ops[start..end].iter().filter(|op| op.0 != '+').count();
+        let b_len = ops[start..end].iter().filter(|op| op.0 != '-').count();
+        if a_len > 0 { a_pos += 1; }
+        if b_len > 0 { b_pos += 1; }
+        out.push_str(&format!("{}\n", format!("@@ -{},{} +{},{} @@", a_pos, a_len,
+                                              b_pos, b_len).paint(Color::Cyan)));
+        for op in ops[start..end].iter() {
+            match op.0 {
+                '-' => { out.push_str(&format!("{}\n",
+                                               format!("-{}", a[op.1]).paint(Color::Red))) },
+                '+' => { out.push_str(&format!("{}\n",
+                                               format!("+{}", b[op.2]).paint(Color::Green))) },
+                _ => { out.push_str(&format!(" {}\n", a[op.1])) },
+            }
+        }
+        i = end;
+    }
+    out
+}
+
+// An operation (' ', '-' or '+') and the positions in `a` and `b` it occurs at
+type DiffOp = (char, usize, usize);
+
+// Line diff using a longest common subsequence table
+fn diff_lines(a: &[&str], b: &[&str]) -> Vec<DiffOp> {
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 }
+                        else { cmp::max(lcs[i + 1][j], lcs[i][j + 1]) };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut ops = Vec::new();
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            ops.push((' ', i, j));
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(('-', i, j));
+            i += 1;
+        } else {
+            ops.push(('+', i, j));
+            j += 1;
+        }
+    }
+    ops
+}
+
+fn updating() -> bool {
+    match env::var(UPDATE_SNAPSHOTS) {
+        Ok(v) => { !v.is_empty() && v != "0" },
+        Err(_) => { false },
+    }
+}
+
+// Replaces runs matching `pattern`, where 'd' stands for any ASCII digit, with
+// `repl`. Fractional seconds following a match are consumed if `frac` is set.
+fn redact_pattern(s: &str, pattern: &str, repl: &str, frac: bool) -> String {
+    let text: Vec<char> = s.chars().collect();
+    let pat: Vec<char> = pattern.chars().collect();
+    let digit_at = |i: usize| i < text.len() && text[i].is_ascii_digit();
+    let mut out = String::with_capacity(s.len());
+    let mut i = 0;
+    while i < text.len() {
+        let matched = i + pat.len() <= text.len()
+            && (i == 0 || !text[i - 1].is_ascii_digit())
+            && !digit_at(i + pat.len())
+            && pat.iter().enumerate().all(|(k, p)| {
+                if *p == 'd' { text[i + k].is_ascii_digit() } else { text[i + k] == *p }
+            });
+        if matched {
+            out.push_str(repl);
+            i += pat.len();
+            if frac && i < text.len() && text[i] == '.' && digit_at(i + 1) {
+                i += 1;
+                while digit_at(i) { i += 1; }
+            }
+        } else {
+            out.push(text[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
 #[test]
 #[should_panic]
 fn test_test_remove() {
@@ -151,3 +690,60 @@ fn test_test_create_symlink() {
     create_symlink(&String::from("LICENSE"), &String::from("test-symlink"));
     remove("test-symlink");
 }
+
+#[test]
+fn test_test_diff() {
+    let diff = diff("a\nb\nc\n", "a\nB\nc\n");
+    assert!(diff.contains(&"@@ -1,3 +1,3 @@".paint(Color::Cyan).to_string()));
+    assert!(diff.contains(&"-b".paint(Color::Red).to_string()));
+    assert!(diff.contains(&"+B".paint(Color::Green).to_string()));
+}
+
+#[test]
+fn test_test_snapshot_redact() {
+    let snap = Snapshot::new("test-snap").redact_timestamps().redact_temp();
+    let temp = env::temp_dir().join("out.txt");
+    assert_eq!(snap.apply(&format!("at 2015-08-01 12:30:45.123 wrote {}", temp.as_str())),
+               format!("at [DATE] [TIME] wrote [TEMP]{}out.txt", MAIN_SEPARATOR));
+}
+
+#[test]
+fn test_test_snapshot_mismatch() {
+    // Mismatches would otherwise be written as the new snapshots
+    env::remove_var(UPDATE_SNAPSHOTS);
+    create_dir("test-snap-mismatch");
+    create_file("test-snap-mismatch/out.snap");
+    let snap = Snapshot::new("test-snap-mismatch");
+    assert!(snap.check("out", "").is_ok());
+    assert!(snap.check("out", "unexpected\n").is_err());
+    assert!(snap.check("missing", "").is_err());
+    remove("test-snap-mismatch");
+}
+
+#[test]
+fn test_test_transcript_parse() {
+    let cases = Transcript::parse("about\n$ util 'a b'\nout\n\n? 3\n\n$ util\n");
+    assert_eq!(cases, vec![
+        TranscriptCase { line: 2, command: "util 'a b'".to_string(),
+                         output: "out\n".to_string(), status: 3 },
+        TranscriptCase { line: 7, command: "util".to_string(),
+                         output: String::new(), status: 0 },
+    ]);
+}
+
+#[test]
+#[cfg(target_family = "unix")]
+fn test_test_transcript_run() {
+    let trans = Transcript::new("test-transcript").file("dir/a.txt", "contents\n");
+    assert!(trans.run_str("$ cat dir/a.txt\ncontents\n$ pwd\n[WORK]\n").is_ok());
+    assert!(trans.run_str("$ cat dir/a.txt\nother\n").is_err());
+    assert!(trans.run_str("$ cat missing.txt\n").is_err());
+    let trans = trans.file("prompt.txt", "one\n$ two\n").file("status.txt", "? 3\n");
+    assert!(trans.run_str("$ cat prompt.txt\none\n").is_err());
+    assert!(trans.run_str("$ cat status.txt\n").is_err());
+    assert!(!Path::new("test-transcript").exists());
+    create_file("test-transcript-keep.txt");
+    assert!(Transcript::new(".").run_str("$ ls test-transcript-keep.txt\n").is_err());
+    assert!(Path::new("test-transcript-keep.txt").is_file());
+    remove("test-transcript-keep.txt");
+}
@@ -0,0 +1,292 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Measuring text as it is displayed on a terminal
+//!
+//! Widths count terminal columns: ANSI escape sequences such as those produced
+//! by `Styled` take none, combining marks take none and East Asian wide
+//! characters take two.
+
+// Ranges of characters taking two columns
+static WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115F), (0x231A, 0x231B), (0x2329, 0x232A), (0x23E9, 0x23EC),
+    (0x2E80, 0x303E), (0x3041, 0x33FF), (0x3400, 0x4DBF), (0x4E00, 0x9FFF),
+    (0xA000, 0xA4CF), (0xA960, 0xA97F), (0xAC00, 0xD7A3), (0xF900, 0xFAFF),
+    (0xFE10, 0xFE19), (0xFE30, 0xFE6F), (0xFF00, 0xFF60), (0xFFE0, 0xFFE6),
+    (0x1F300, 0x1F64F), (0x1F900, 0x1F9FF), (0x20000, 0x2FFFD), (0x30000, 0x3FFFD),
+];
+
+// Ranges of characters taking no columns
+static ZERO: &[(u32, u32)] = &[
+    (0x0300, 0x036F), (0x0483, 0x0489), (0x0591, 0x05BD), (0x0610, 0x061A),
+    (0x064B, 0x065F), (0x0E31, 0x0E31), (0x0E34, 0x0E3A), (0x1AB0, 0x1AFF),
+    (0x1DC0, 0x1DFF), (0x200B, 0x200F), (0x20D0, 0x20FF), (0xFE00, 0xFE0F),
+    (0xFE20, 0xFE2F),
+];
+
+fn in_ranges(c: char, ranges: &[(u32, u32)]) -> bool {
+    let c = c as u32;
+    ranges.iter().any(|&(lo, hi)| c >= lo && c <= hi)
+}
+
+/// Returns the number of columns `c` takes
+///
+/// # Example
+/// ```
+/// use rpf::text;
+///
+/// assert_eq!(text::char_width('a'), 1);
+/// assert_eq!(text::char_width('漢'), 2);
+/// assert_eq!(text::char_width('\u{301}'), 0);
+/// ```
+pub fn char_width(c: char) -> usize {
+    if c.is_control() || in_ranges(c, ZERO) { 0 }
+    else if in_ranges(c, WIDE) { 2 }
+    else { 1 }
+}
+
+// Returns the length of the escape sequence at the start of `s`, 0 if there
+// is none
+fn escape_len(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    if bytes.len() < 2 || bytes[0] != 0x1b { return 0; }
+    match bytes[1] {
+        b'[' => {
+            match bytes[2..].iter().position(|&b| (0x40..=0x7e).contains(&b)) {
+                Some(i) => { i + 3 },
+                None => { bytes.len() },
+            }
+        },
+        b']' => {
+            for i in 2..bytes.len() {
+                if bytes[i] == 0x07 { return i + 1; }
+                if bytes[i] == 0x1b && bytes.get(i + 1) == Some(&b'\\') { return i + 2; }
+            }
+            bytes.len()
+        },
+        // ESC followed by any other character, which may take several bytes
+        _ => { 1 + s[1..].chars().next().map(|c| c.len_utf8()).unwrap_or(0) },
+    }
+}
+
+// Splits `s` into escape sequences and text, calling `f` with each piece and
+// whether it is an escape sequence
+fn pieces<'a, F: FnMut(&'a str, bool)>(s: &'a str, mut f: F) {
+    let mut rest = s;
+    while !rest.is_empty() {
+        let n = escape_len(rest);
+        if n > 0 {
+            f(&rest[..n], true);
+            rest = &rest[n..];
+        } else {
+            let first = rest.chars().next().map(|c| c.len_utf8()).unwrap_or(1);
+            let end = rest[first..].find('\x1b').map(|i| i + first).unwrap_or(rest.len());
+            f(&rest[..end], false);
+            rest = &rest[end..];
+        }
+    }
+}
+
+/// Returns `s` without ANSI escape sequences
+///
+/// # Example
+/// ```
+/// use rpf::{Color,Styled,text};
+///
+/// assert_eq!(text::strip_ansi(&"red".paint(Color::Red).to_string()), "red");
+/// ```
+pub fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    pieces(s, |p, esc| if !esc { out.push_str(p); });
+    out
+}
+
+/// Returns the number of columns `s` takes on a terminal
+///
+/// # Example
+/// ```
+/// use rpf::{Styled,text};
+///
+/// assert_eq!(text::width(&"bold".bold().to_string()), 4);
+/// assert_eq!(text::width("日本語"), 6);
+/// ```
+pub fn width(s: &str) -> usize {
+    let mut w = 0;
+    pieces(s, |p, esc| if !esc { w += p.chars().map(char_width).sum::<usize>(); });
+    w
+}
+
+/// Shortens `s` to at most `max` columns, ending it with `…` if anything was
+/// cut. Escape sequences are kept and styling is reset after a cut.
+///
+/// # Example
+/// ```
+/// use rpf::text;
+///
+/// assert_eq!(text::truncate("abcdef", 4), "abc…");
+/// assert_eq!(text::truncate("漢字漢字", 5), "漢字…");
+/// assert_eq!(text::truncate("abc", 4), "abc");
+/// ```
+pub fn truncate(s: &str, max: usize) -> String {
+    if width(s) <= max { return s.to_string(); }
+    if max == 0 { return String::new(); }
+    let mut out = String::new();
+    let mut used = 0;
+    let mut styled = false;
+    let mut full = false;
+    pieces(s, |p, esc| {
+        if esc {
+            if !full { out.push_str(p); styled = true; }
+            return;
+        }
+        for c in p.chars() {
+            if full { return; }
+            let w = char_width(c);
+            if used + w > max - 1 { full = true; return; }
+            out.push(c);
+            used += w;
+        }
+    });
+    out.push('…');
+    if styled { out.push_str("\x1b[0m"); }
+    out
+}
+
+/// Pads `s` with spaces on the right to `w` columns
+///
+/// # Example
+/// ```
+/// use rpf::text;
+///
+/// assert_eq!(text::pad("漢", 4), "漢  ");
+/// ```
+pub fn pad(s: &str, w: usize) -> String {
+    format!("{}{}", s, " ".repeat(w.saturating_sub(width(s))))
+}
+
+/// Wraps `s` into lines of at most `max` columns, breaking between words and
+/// within words longer than a line. Styling from escape sequences is reset at
+/// the end of each line and restored at the start of the next, so lines can be
+/// printed side by side.
+///
+/// # Example
+/// ```
+/// use rpf::text;
+///
+/// assert_eq!(text::wrap("the quick brown fox", 10), vec!["the quick", "brown fox"]);
+/// assert_eq!(text::wrap("abcdefgh", 3), vec!["abc", "def", "gh"]);
+/// ```
+pub fn wrap(s: &str, max: usize) -> Vec<String> {
+    wrap_lines(s, max, max)
+}
+
+/// Wraps `s` to `width` columns like `wrap`, starting the first line with
+/// `first` and the others with `rest`, which gives hanging indents. Every line
+/// ends with a newline.
+///
+/// # Example
+/// ```
+/// use rpf::text;
+///
+/// assert_eq!(text::fill("Print more information about everything", 40, "  -v  ", "      "),
+///            "  -v  Print more information about\n      everything\n");
+/// ```
+pub fn fill(s: &str, width: usize, first: &str, rest: &str) -> String {
+    let lines = wrap_lines(s, width.saturating_sub(self::width(first)),
+                           width.saturating_sub(self::width(rest)));
+    let mut out = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        out.push_str(if i == 0 { first } else { rest });
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+// Wraps `s` with `first` columns for the first line and `rest` for the others
+fn wrap_lines(s: &str, first: usize, rest: usize) -> Vec<String> {
+    let first = first.max(1);
+    let rest = rest.max(1);
+    let mut lines: Vec<String> = Vec::new();
+    for para in s.split('\n') {
+        let mut line = String::new();
+        let mut used = 0;
+        for word in para.split(' ').filter(|w| !w.is_empty()) {
+            let max = if lines.is_empty() { first } else { rest };
+            let w = width(word);
+            if used > 0 && used + 1 + w <= max {
+                line.push(' ');
+                line.push_str(word);
+                used += 1 + w;
+                continue;
+            }
+            if used > 0 {
+                lines.push(::std::mem::take(&mut line));
+                used = 0;
+            }
+            let max = if lines.is_empty() { first } else { rest };
+            if w <= max {
+                line.push_str(word);
+                used = w;
+                continue;
+            }
+            pieces(word, |p, esc| {
+                if esc { line.push_str(p); return; }
+                for c in p.chars() {
+                    let max = if lines.is_empty() { first } else { rest };
+                    let cw = char_width(c);
+                    if used + cw > max && used > 0 {
+                        lines.push(::std::mem::take(&mut line));
+                        used = 0;
+                    }
+                    line.push(c);
+                    used += cw;
+                }
+            });
+        }
+        lines.push(line);
+    }
+    restyle(lines)
+}
+
+// Closes styling at the end of each line and reopens it on the next
+fn restyle(lines: Vec<String>) -> Vec<String> {
+    let mut active: Vec<String> = Vec::new();
+    lines.into_iter().map(|line| {
+        let mut out = active.concat();
+        pieces(&line, |p, esc| {
+            if esc && p.starts_with("\x1b[") && p.ends_with('m') {
+                if p == "\x1b[0m" || p == "\x1b[m" { active.clear(); }
+                else { active.push(p.to_string()); }
+            }
+        });
+        out.push_str(&line);
+        if !active.is_empty() { out.push_str("\x1b[0m"); }
+        out
+    }).collect()
+}
+
+#[test]
+fn test_text_wrap_styled() {
+    assert_eq!(wrap("\x1b[31mred text\x1b[0m", 4),
+               vec!["\x1b[31mred\x1b[0m", "\x1b[31mtext\x1b[0m"]);
+    assert_eq!(wrap("a\n\nb", 5), vec!["a", "", "b"]);
+    assert_eq!(wrap("漢字漢字", 4), vec!["漢字", "漢字"]);
+}
+
+#[test]
+fn test_text_width_escapes() {
+    assert_eq!(width("\x1b[1;31mred\x1b[0m"), 3);
+    assert_eq!(width("\x1b]8;;http://x\x07link\x1b]8;;\x07"), 4);
+    assert_eq!(width("e\u{301}"), 1);
+    assert_eq!(width("🦀"), 2);
+    assert_eq!(width("\x1bé"), 0);
+    assert_eq!(strip_ansi("a\x1b漢b"), "ab");
+}
+
+#[test]
+fn test_text_truncate_styled() {
+    assert_eq!(truncate("\x1b[31mabcdef\x1b[0m", 3), "\x1b[31mab…\x1b[0m");
+    assert_eq!(truncate("abc", 0), "");
+}
@@ -0,0 +1,308 @@
+// Copyright (C) 2015, Alberto Corona <ac@albertocorona.com>
+// All rights reserved. This file is part of rpf, distributed under the
+// BSD 3-Clause license. For full terms please see the LICENSE file.
+
+//! Parsers for option values
+//!
+//! A parser is any `Fn(&str) -> Result<T, String>` where the error is the
+//! reason the value was rejected. Parsers are used with `Matches::parse_with`
+//! or `Prog::value`, which add the option name and the environment variable
+//! the value came from to the message.
+//!
+//! ```
+//! use rpf::{Cmd,Opt,value};
+//!
+//! let cmd = Cmd::new("util").opt(Opt::value("jobs", "N", "Run N jobs"));
+//! let m = cmd.parse(vec!["--jobs", "0"]).unwrap();
+//! assert_eq!(m.parse_with("jobs", value::range(1, 64)).unwrap_err().mesg,
+//!            "Invalid value '0' for '--jobs': must be between 1 and 64");
+//! ```
+
+use utils::AsPath;
+
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Returns a parser for numbers from `min` to `max` inclusive
+///
+/// # Example
+/// ```
+/// use rpf::value;
+///
+/// let port = value::range(1u16, 65535);
+/// assert_eq!(port("8080"), Ok(8080));
+/// assert_eq!(port("0"), Err("must be between 1 and 65535".to_string()));
+/// assert_eq!(port("http"), Err("not a number".to_string()));
+/// ```
+pub fn range<T>(min: T, max: T) -> impl Fn(&str) -> Result<T, String>
+    where T: FromStr + PartialOrd + Display {
+    move |s| {
+        let n = number::<T>(s)?;
+        if n < min || n > max {
+            return Err(format!("must be between {} and {}", min, max));
+        }
+        Ok(n)
+    }
+}
+
+/// Parses any number `T` can hold
+///
+/// # Example
+/// ```
+/// use rpf::value;
+///
+/// assert_eq!(value::number::<i32>("-3"), Ok(-3));
+/// assert!(value::number::<u8>("256").is_err());
+/// ```
+pub fn number<T: FromStr>(s: &str) -> Result<T, String> {
+    let s = s.trim();
+    // Only a sign and digits can be a number that does not fit
+    let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+    match s.parse::<T>() {
+        Ok(n) => { Ok(n) },
+        Err(_) if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) => {
+            Err("number out of range".to_string())
+        },
+        Err(_) => { Err("not a number".to_string()) },
+    }
+}
+
+/// Parses any type implementing `FromStr`, using its error as the reason
+///
+/// # Example
+/// ```
+/// use rpf::value;
+/// use std::net::IpAddr;
+///
+/// assert!(value::parsed::<IpAddr>("::1").is_ok());
+/// assert_eq!(value::parsed::<IpAddr>("local").unwrap_err(), "invalid IP address syntax");
+/// ```
+pub fn parsed<T>(s: &str) -> Result<T, String> where T: FromStr, T::Err: Display {
+    s.parse::<T>().map_err(|e| e.to_string())
+}
+
+/// Parses a boolean, `true`, `yes`, `on` and `1` or `false`, `no`, `off` and
+/// `0`, ignoring case
+///
+/// # Example
+/// ```
+/// use rpf::value;
+///
+/// assert_eq!(value::boolean("Yes"), Ok(true));
+/// assert_eq!(value::boolean("off"), Ok(false));
+/// assert!(value::boolean("maybe").is_err());
+/// ```
+pub fn boolean(s: &str) -> Result<bool, String> {
+    match &s.trim().to_lowercase()[..] {
+        "1" | "true" | "yes" | "on" => { Ok(true) },
+        "0" | "false" | "no" | "off" => { Ok(false) },
+        _ => { Err("expected yes or no".to_string()) },
+    }
+}
+
+/// Parses a duration made of numbers with the units `ms`, `s`, `m`, `h` and
+/// `d`, such as `10s`, `1.5h` or `1h 30m`. A number without a unit is in
+/// seconds.
+///
+/// # Example
+/// ```
+/// use rpf::value;
+/// use std::time::Duration;
+///
+/// assert_eq!(value::duration("5m"), Ok(Duration::from_secs(300)));
+/// assert_eq!(value::duration("1h30m"), Ok(Duration::from_secs(5400)));
+/// assert_eq!(value::duration("250ms"), Ok(Duration::from_millis(250)));
+/// assert_eq!(value::duration("10"), Ok(Duration::from_secs(10)));
+/// ```
+pub fn duration(s: &str) -> Result<Duration, String> {
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err("expected a duration such as 10s or 5m".to_string());
+    }
+    let mut total = 0f64;
+    while !rest.is_empty() {
+        let (num, unit, tail) = split_unit(rest);
+        let unit = unit.trim();
+        let n = match num.parse::<f64>() {
+            Ok(n) if !num.is_empty() => { n },
+            _ => { return Err("expected a duration such as 10s or 5m".to_string()); },
+        };
+        let scale = match unit {
+            "ms" => { 0.001 },
+            "" | "s" => { 1.0 },
+            "m" => { 60.0 },
+            "h" => { 3600.0 },
+            "d" => { 86400.0 },
+            _ => { return Err(format!("unknown unit '{}', expected ms, s, m, h or d", unit)); },
+        };
+        total += n * scale;
+        rest = tail.trim_start();
+    }
+    if !total.is_finite() || total >= u64::MAX as f64 {
+        return Err("duration too long".to_string());
+    }
+    Ok(Duration::from_secs_f64(total))
+}
+
+/// Parses a size in bytes. `K`, `M`, `G`, `T` and `P` and the IEC suffixes
+/// `KiB`, `MiB` and so on are powers of 1024, the SI suffixes `kB`, `KB`, `MB`
+/// and so on are powers of 1000. A trailing `B` is optional for plain bytes.
+///
+/// # Example
+/// ```
+/// use rpf::value;
+///
+/// assert_eq!(value::bytes("10K"), Ok(10240));
+/// assert_eq!(value::bytes("2MiB"), Ok(2097152));
+/// assert_eq!(value::bytes("1.5kB"), Ok(1500));
+/// assert_eq!(value::bytes("512"), Ok(512));
+/// ```
+pub fn bytes(s: &str) -> Result<u64, String> {
+    let (num, unit, tail) = split_unit(s.trim());
+    let n = match num.parse::<f64>() {
+        Ok(n) if !num.is_empty() && tail.is_empty() => { n },
+        _ => { return Err("expected a size such as 10K or 2MiB".to_string()); },
+    };
+    let unit = unit.trim();
+    let (prefix, base) = if let Some(p) = unit.strip_suffix("iB") {
+        (p, 1024f64)
+    } else if let Some(p) = unit.strip_suffix('B').filter(|p| !p.is_empty()) {
+        (p, 1000f64)
+    } else {
+        (unit.strip_suffix('B').unwrap_or(unit), 1024f64)
+    };
+    let power = match &prefix.to_uppercase()[..] {
+        "" => { 0 },
+        "K" => { 1 },
+        "M" => { 2 },
+        "G" => { 3 },
+        "T" => { 4 },
+        "P" => { 5 },
+        "E" => { 6 },
+        _ => { return Err(format!("unknown unit '{}'", unit)); },
+    };
+    let total = n * base.powi(power);
+    if total < 0.0 || total >= u64::MAX as f64 {
+        return Err("size out of range".to_string());
+    }
+    Ok(total as u64)
+}
+
+/// Returns a parser accepting only `choices`
+///
+/// # Example
+/// ```
+/// use rpf::value;
+///
+/// let when = value::choice(&["always", "auto", "never"]);
+/// assert_eq!(when("auto"), Ok("auto"));
+/// assert_eq!(when("sometimes"), Err("expected one of always, auto, never".to_string()));
+/// ```
+pub fn choice(choices: &'static [&'static str]) -> impl Fn(&str) -> Result<&'static str, String> {
+    move |s| {
+        match choices.iter().find(|c| **c == s) {
+            Some(c) => { Ok(*c) },
+            None => { Err(format!("expected one of {}", choices.join(", "))) },
+        }
+    }
+}
+
+/// Returns a parser mapping fixed names to values, such as the variants of an
+/// enum
+///
+/// # Example
+/// ```
+/// use rpf::value;
+///
+/// #[derive(Copy,Clone,Debug,PartialEq)]
+/// enum When { Always, Never }
+///
+/// let when = value::one_of(&[("always", When::Always), ("never", When::Never)]);
+/// assert_eq!(when("never"), Ok(When::Never));
+/// assert!(when("auto").is_err());
+/// ```
+pub fn one_of<T: Copy>(choices: &'static [(&'static str, T)])
+                       -> impl Fn(&str) -> Result<T, String> {
+    move |s| {
+        match choices.iter().find(|c| c.0 == s) {
+            Some(c) => { Ok(c.1) },
+            None => {
+                let names: Vec<&str> = choices.iter().map(|c| c.0).collect();
+                Err(format!("expected one of {}", names.join(", ")))
+            },
+        }
+    }
+}
+
+/// Parses the path of an existing file
+///
+/// # Example
+/// ```
+/// use rpf::value;
+///
+/// assert!(value::file("Cargo.toml").is_ok());
+/// assert_eq!(value::file("src"), Err("not a file".to_string()));
+/// ```
+pub fn file(s: &str) -> Result<PathBuf, String> {
+    let path = s.as_pathbuf();
+    if path.is_file() { Ok(path) }
+    else if path.exists() { Err("not a file".to_string()) }
+    else { Err("no such file".to_string()) }
+}
+
+/// Parses the path of an existing directory
+///
+/// # Example
+/// ```
+/// use rpf::value;
+///
+/// assert!(value::dir("src").is_ok());
+/// assert_eq!(value::dir("Cargo.toml"), Err("not a directory".to_string()));
+/// ```
+pub fn dir(s: &str) -> Result<PathBuf, String> {
+    let path = s.as_pathbuf();
+    if path.is_dir() { Ok(path) }
+    else if path.exists() { Err("not a directory".to_string()) }
+    else { Err("no such directory".to_string()) }
+}
+
+// Splits a leading number from its unit and whatever follows the unit
+fn split_unit(s: &str) -> (&str, &str, &str) {
+    let num_end = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+    let rest = &s[num_end..];
+    let unit_end = rest.find(|c: char| c.is_ascii_digit() || c == '.').unwrap_or(rest.len());
+    (&s[..num_end], &rest[..unit_end], &rest[unit_end..])
+}
+
+#[test]
+fn test_value_number_errors() {
+    assert_eq!(number::<u8>("-"), Err::<u8, _>("not a number".to_string()));
+    assert_eq!(number::<i8>("+"), Err::<i8, _>("not a number".to_string()));
+    assert_eq!(number::<i8>("--5"), Err::<i8, _>("not a number".to_string()));
+    assert_eq!(number::<i8>("+200"), Err::<i8, _>("number out of range".to_string()));
+    assert_eq!(number::<u8>("-1"), Err::<u8, _>("number out of range".to_string()));
+}
+
+#[test]
+fn test_value_duration_errors() {
+    assert!(duration("").is_err());
+    assert!(duration("s").is_err());
+    assert_eq!(duration("3w"), Err("unknown unit 'w', expected ms, s, m, h or d".to_string()));
+    assert_eq!(duration("1.5h"), Ok(Duration::from_secs(5400)));
+    assert_eq!(duration("1h 30m"), Ok(Duration::from_secs(5400)));
+    assert_eq!(duration("18446744073709551616"), Err("duration too long".to_string()));
+}
+
+#[test]
+fn test_value_bytes_units() {
+    assert_eq!(bytes("1"), Ok(1));
+    assert_eq!(bytes("1B"), Ok(1));
+    assert_eq!(bytes("1k"), Ok(1024));
+    assert_eq!(bytes("1KB"), Ok(1000));
+    assert_eq!(bytes("1GiB"), Ok(1 << 30));
+    assert_eq!(bytes("1 MB"), Ok(1000000));
+    assert!(bytes("1XB").is_err());
+    assert!(bytes("100E").is_err());
+}